@@ -0,0 +1,55 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use actix_web::error::{ErrorForbidden, ErrorUnauthorized};
+use actix_web::HttpRequest;
+use actix_web::http::header;
+use lib::index::{IndexAction, IndexTopicMessage};
+
+// admin endpoints are only served when `ADMIN_TOKEN` is set, to requests with `Authorization: Bearer <token>`.
+pub fn check_admin(req: &HttpRequest) -> actix_web::Result<()> {
+    let token = dotenv::var("ADMIN_TOKEN")
+        .map_err(|_| ErrorForbidden("admin endpoints are disabled"))?;
+
+    let authorized = req.headers().get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v == token)
+        .unwrap_or(false);
+
+    if authorized {
+        Ok(())
+    } else {
+        Err(ErrorUnauthorized("invalid admin token"))
+    }
+}
+
+// asks the index function to remove the documents of the video from one language, or every language.
+pub async fn publish_index_delete(video_id: &str, lang: Option<&str>) -> Result<(), anyhow::Error> {
+    let topic = lib::queue::topic_from_env("INDEX_TOPIC_ARN").await;
+
+    // an empty `lang` is every language.
+    topic.publish(&serde_json::to_string(&IndexTopicMessage {
+        video_id: video_id.to_string(),
+        lang: lang.unwrap_or_default().to_string(),
+        action: IndexAction::Delete,
+        ..Default::default()
+    })?).await?;
+
+    Ok(())
+}
\ No newline at end of file
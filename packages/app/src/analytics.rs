@@ -0,0 +1,358 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use actix_web::web;
+use aws_sdk_dynamodb::model::AttributeValue;
+use serde::{Serialize, Deserialize};
+use serde_dynamo::{from_items, to_item};
+use time::{Duration, OffsetDateTime};
+
+// entries are kept this long in the table.
+const RETENTION_DAYS: i64 = 90;
+// longest clicked time accepted, e.g. `01:02:03.000`.
+const MAX_CLICK_TIME_LEN: usize = 12;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    Search,
+    Click,
+}
+
+// one line of the log. a click shares the `search_id` of the search it came from and only
+// carries the clicked result.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SearchLogEntry {
+    pub search_id: String,
+    pub kind: EntryKind,
+    // unix time in milliseconds
+    pub timestamp: i64,
+    #[serde(default)]
+    pub lang: String,
+    #[serde(default)]
+    pub query: String,
+    pub video_id: Option<String>,
+    #[serde(default)]
+    pub hits: usize,
+    #[serde(default)]
+    pub latency_ms: u64,
+    pub clicked_video_id: Option<String>,
+    pub clicked_time: Option<String>,
+}
+
+impl SearchLogEntry {
+    pub fn search(lang: &str, query: &str, video_id: Option<&str>, hits: usize, latency_ms: u64) -> Self {
+        SearchLogEntry {
+            search_id: uuid::Uuid::new_v4().to_string(),
+            kind: EntryKind::Search,
+            timestamp: now_millis(),
+            lang: lang.to_string(),
+            query: query.to_string(),
+            video_id: video_id.map(|s| s.to_string()),
+            hits,
+            latency_ms,
+            clicked_video_id: None,
+            clicked_time: None,
+        }
+    }
+
+    // clicks are reported by clients, so only the ids and times the app hands out are taken.
+    pub fn click(search_id: &str, video_id: &str, time: Option<&str>) -> Option<Self> {
+        let valid_time = |t: &str| t.len() <= MAX_CLICK_TIME_LEN && t.chars().all(|c| c.is_ascii_digit() || c == ':' || c == '.');
+        if uuid::Uuid::parse_str(search_id).is_err()
+            || lib::storage::check_name(video_id).is_err()
+            || matches!(time, Some(t) if !valid_time(t)) {
+            return None;
+        }
+
+        Some(SearchLogEntry {
+            search_id: search_id.to_string(),
+            kind: EntryKind::Click,
+            timestamp: now_millis(),
+            lang: String::new(),
+            query: String::new(),
+            video_id: None,
+            hits: 0,
+            latency_ms: 0,
+            clicked_video_id: Some(video_id.to_string()),
+            clicked_time: time.map(|s| s.to_string()),
+        })
+    }
+}
+
+fn now_millis() -> i64 {
+    (OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+// `ANALYTICS_TABLE_NAME` logs to DynamoDB, `ANALYTICS_LOG` to a local JSONL file,
+// and searches are not recorded without either. it is created once and shared by the handlers
+// as app data.
+pub enum SearchLog {
+    DynamoDb(aws_sdk_dynamodb::Client, String),
+    File(PathBuf),
+    Disabled,
+}
+
+impl SearchLog {
+    pub async fn from_env() -> Self {
+        if let Ok(table_name) = dotenv::var("ANALYTICS_TABLE_NAME") {
+            let shared_config = aws_config::from_env().load().await;
+            SearchLog::DynamoDb(aws_sdk_dynamodb::Client::new(&shared_config), table_name)
+        } else if let Ok(path) = dotenv::var("ANALYTICS_LOG") {
+            SearchLog::File(PathBuf::from(path))
+        } else {
+            SearchLog::Disabled
+        }
+    }
+
+    pub async fn append(&self, entry: &SearchLogEntry) -> Result<(), anyhow::Error> {
+        match self {
+            SearchLog::DynamoDb(dynamodb, table_name) => {
+                // partitioned by day, so a report only reads the days it covers.
+                let created = OffsetDateTime::from_unix_timestamp_nanos(entry.timestamp as i128 * 1_000_000)?;
+                let mut item: HashMap<String, AttributeValue> = to_item(entry)?;
+                item.insert("day".to_string(), AttributeValue::S(created.date().to_string()));
+                item.insert("sk".to_string(), AttributeValue::S(
+                    format!("{}#{}#{:?}", entry.timestamp, entry.search_id, entry.kind)));
+                item.insert("expires_at".to_string(), AttributeValue::N(
+                    (created + Duration::days(RETENTION_DAYS)).unix_timestamp().to_string()));
+
+                dynamodb.put_item()
+                    .table_name(table_name)
+                    .set_item(Some(item))
+                    .send()
+                    .await?;
+            }
+            SearchLog::File(path) => {
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)?;
+                writeln!(file, "{}", serde_json::to_string(entry)?)?;
+            }
+            SearchLog::Disabled => {}
+        }
+
+        Ok(())
+    }
+
+    // entries of the last `days` times 24 hours.
+    pub async fn entries(&self, days: i64) -> Result<Vec<SearchLogEntry>, anyhow::Error> {
+        let now = OffsetDateTime::now_utc();
+        let since = (now - Duration::days(days)).unix_timestamp() * 1000;
+
+        match self {
+            SearchLog::DynamoDb(dynamodb, table_name) => {
+                // the oldest day partition read is the one `since` falls on.
+                let mut entries = vec![];
+                for offset in 0..=days.max(0) {
+                    let day = (now.date() - Duration::days(offset)).to_string();
+                    let mut start_key = None;
+                    loop {
+                        let output = dynamodb.query()
+                            .table_name(table_name)
+                            .key_condition_expression("#day = :day")
+                            .expression_attribute_names("#day", "day")
+                            .expression_attribute_values(":day", AttributeValue::S(day.clone()))
+                            .set_exclusive_start_key(start_key)
+                            .send()
+                            .await?;
+
+                        let items: Vec<SearchLogEntry> = from_items(output.items.unwrap_or_default())?;
+                        entries.extend(items.into_iter().filter(|entry| entry.timestamp >= since));
+
+                        start_key = output.last_evaluated_key;
+                        if start_key.is_none() {
+                            break;
+                        }
+                    }
+                }
+
+                Ok(entries)
+            }
+            SearchLog::File(path) => {
+                if !path.exists() {
+                    return Ok(vec![]);
+                }
+
+                let mut entries = vec![];
+                for line in BufReader::new(std::fs::File::open(path)?).lines() {
+                    let line = line?;
+                    // a line cut off by a crash is skipped, the rest of the log is still usable.
+                    if let Ok(entry) = serde_json::from_str::<SearchLogEntry>(&line) {
+                        if entry.timestamp >= since {
+                            entries.push(entry);
+                        }
+                    }
+                }
+
+                Ok(entries)
+            }
+            SearchLog::Disabled => Ok(vec![]),
+        }
+    }
+}
+
+// records the entry without holding up the response, a failed write is only logged. on Lambda a
+// write still pending when the response is sent completes while the function handles a later request.
+pub fn record(log: &web::Data<SearchLog>, entry: SearchLogEntry) {
+    let log = log.clone();
+    actix_web::rt::spawn(async move {
+        if let Err(e) = log.append(&entry).await {
+            println!("failed to record search: {}", e);
+        }
+    });
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct QueryCount {
+    pub query: String,
+    pub searches: usize,
+    pub clicks: usize,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct LangReport {
+    pub searches: usize,
+    pub zero_result_searches: usize,
+    pub avg_latency_ms: u64,
+    pub top_queries: Vec<QueryCount>,
+    pub zero_result_queries: Vec<QueryCount>,
+}
+
+#[derive(Default)]
+struct LangCounts {
+    report: LangReport,
+    latency_ms: u64,
+    queries: HashMap<String, QueryCount>,
+    zero_result: HashMap<String, QueryCount>,
+}
+
+// most searched and zero result queries per language. queries are compared case-insensitively
+// and a search counts as clicked once, however many results were opened.
+pub fn report(entries: &[SearchLogEntry], limit: usize) -> BTreeMap<String, LangReport> {
+    let clicked = entries.iter()
+        .filter(|e| e.kind == EntryKind::Click)
+        .map(|e| e.search_id.as_str())
+        .collect::<HashSet<_>>();
+
+    let mut langs: BTreeMap<String, LangCounts> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e.kind == EntryKind::Search) {
+        let counts = langs.entry(entry.lang.clone()).or_default();
+        let query = entry.query.trim().to_lowercase();
+        let click = clicked.contains(entry.search_id.as_str()) as usize;
+
+        counts.report.searches += 1;
+        counts.latency_ms += entry.latency_ms;
+        count_query(&mut counts.queries, &query, click);
+        if entry.hits == 0 {
+            counts.report.zero_result_searches += 1;
+            count_query(&mut counts.zero_result, &query, click);
+        }
+    }
+
+    langs.into_iter()
+        .map(|(lang, counts)| {
+            let mut lang_report = counts.report;
+            lang_report.avg_latency_ms = counts.latency_ms / lang_report.searches.max(1) as u64;
+            lang_report.top_queries = top_queries(counts.queries, limit);
+            lang_report.zero_result_queries = top_queries(counts.zero_result, limit);
+            (lang, lang_report)
+        })
+        .collect()
+}
+
+fn count_query(counts: &mut HashMap<String, QueryCount>, query: &str, clicks: usize) {
+    let count = counts.entry(query.to_string())
+        .or_insert_with(|| QueryCount { query: query.to_string(), searches: 0, clicks: 0 });
+    count.searches += 1;
+    count.clicks += clicks;
+}
+
+fn top_queries(counts: HashMap<String, QueryCount>, limit: usize) -> Vec<QueryCount> {
+    let mut counts = counts.into_values().collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.searches.cmp(&a.searches).then_with(|| a.query.cmp(&b.query)));
+    counts.truncate(limit);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analytics::{report, EntryKind, QueryCount, SearchLog, SearchLogEntry};
+
+    #[test]
+    fn report_test() {
+        let search = |lang: &str, query: &str, hits: usize| SearchLogEntry::search(lang, query, None, hits, 10);
+        let clicked = search("en", "Lambda", 3);
+        let entries = vec![
+            SearchLogEntry::click(&clicked.search_id, "a", Some("00:00:01.000")).unwrap(),
+            SearchLogEntry::click(&clicked.search_id, "b", None).unwrap(),
+            clicked,
+            search("en", "lambda ", 1),
+            search("en", "s3", 2),
+            search("en", "fargate", 0),
+            search("en", "fargate", 0),
+            search("ko", "람다", 0),
+        ];
+
+        let reports = report(&entries, 2);
+        assert_eq!(reports.keys().collect::<Vec<_>>(), vec!["en", "ko"]);
+        let en = &reports["en"];
+        assert_eq!((en.searches, en.zero_result_searches, en.avg_latency_ms), (5, 2, 10));
+        // a search counts as clicked once.
+        assert_eq!(en.top_queries, vec![
+            QueryCount { query: "fargate".to_string(), searches: 2, clicks: 0 },
+            QueryCount { query: "lambda".to_string(), searches: 2, clicks: 1 },
+        ]);
+        assert_eq!(en.zero_result_queries, vec![QueryCount { query: "fargate".to_string(), searches: 2, clicks: 0 }]);
+        assert_eq!(reports["ko"].zero_result_queries[0].query, "람다");
+    }
+
+    #[test]
+    fn click_test() {
+        let search_id = uuid::Uuid::new_v4().to_string();
+        let click = SearchLogEntry::click(&search_id, "a-1", Some("01:02:03.000")).unwrap();
+        assert_eq!(click.kind, EntryKind::Click);
+
+        assert!(SearchLogEntry::click("forged", "a", None).is_none());
+        assert!(SearchLogEntry::click(&search_id, "../a", None).is_none());
+        assert!(SearchLogEntry::click(&search_id, "", None).is_none());
+        assert!(SearchLogEntry::click(&search_id, "a", Some("<script>")).is_none());
+        assert!(SearchLogEntry::click(&search_id, "a", Some(&"1".repeat(100))).is_none());
+    }
+
+    #[actix_web::test]
+    async fn file_entries_test() {
+        let path = std::env::temp_dir().join(format!("analytics-test-{}.jsonl", uuid::Uuid::new_v4()));
+        let log = SearchLog::File(path.clone());
+        let mut old = SearchLogEntry::search("en", "old", None, 1, 10);
+        old.timestamp -= 2 * 24 * 3600 * 1000;
+        log.append(&old).await.unwrap();
+        log.append(&SearchLogEntry::search("en", "new", None, 1, 10)).await.unwrap();
+
+        let queries = |entries: Vec<SearchLogEntry>| entries.into_iter().map(|e| e.query).collect::<Vec<_>>();
+        assert_eq!(queries(log.entries(1).await.unwrap()), vec!["new"]);
+        assert_eq!(queries(log.entries(3).await.unwrap()), vec!["old", "new"]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
\ No newline at end of file
@@ -47,11 +47,6 @@ impl SubtitleQueueMessage {
 #[post("/api/video/subtitle")]
 pub async fn handler(req: web::Json<RequestSubtitleRequest>) -> actix_web::Result<HttpResponse> {
 
-    let queue_url = dotenv::var("SUBTITLE_QUEUE_URL")
-        .expect("SUBTITLE_QUEUE_URL must be set.");
-
-    let shared_config = aws_config::from_env().load().await;
-
     if let Ok(video) = get_video(&req.video_id).await {
         let content_language = &video.lang.split("-").collect::<Vec<_>>()[0];
         let msg = SubtitleQueueMessage::new(
@@ -59,11 +54,8 @@ pub async fn handler(req: web::Json<RequestSubtitleRequest>) -> actix_web::Resul
             *content_language,
             req.target_lang.as_ref().map(|l| l.as_str()));
 
-        let sqs = aws_sdk_sqs::Client::new(&shared_config);
-        sqs.send_message()
-            .queue_url(&queue_url)
-            .message_body(serde_json::to_string(&msg).unwrap())
-            .send()
+        let queue = lib::queue::queue_from_env("SUBTITLE_QUEUE_URL").await;
+        queue.send(&serde_json::to_string(&msg).unwrap())
             .await
             .unwrap();
 
@@ -0,0 +1,42 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::{HttpResponse, web};
+use actix_web::post;
+use serde::Deserialize;
+use crate::analytics::{SearchLog, SearchLogEntry};
+
+#[derive(Deserialize)]
+pub struct SearchClickRequest {
+    search_id: String,
+    video_id: String,
+    time: Option<String>,
+}
+
+// a result opened from a search, with the `X-Search-Id` of its response.
+#[post("/api/search/click")]
+pub async fn handler(log: web::Data<SearchLog>, req: web::Json<SearchClickRequest>) -> actix_web::Result<HttpResponse> {
+
+    let entry = SearchLogEntry::click(&req.search_id, &req.video_id, req.time.as_deref())
+        .ok_or_else(|| ErrorBadRequest("invalid click"))?;
+    log.append(&entry).await
+        .map_err(|e| ErrorInternalServerError(e))?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
\ No newline at end of file
@@ -0,0 +1,48 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::get;
+use serde::Deserialize;
+use crate::admin::check_admin;
+use crate::analytics::{report, SearchLog};
+
+const MAX_REPORT_DAYS: i64 = 90;
+
+#[derive(Deserialize)]
+pub struct SearchReportQuery {
+    days: Option<i64>,
+    limit: Option<usize>,
+}
+
+// top queries and zero result queries per language over the last days.
+#[get("/api/admin/search/report")]
+pub async fn handler(req: HttpRequest, log: web::Data<SearchLog>, query: web::Query<SearchReportQuery>) -> actix_web::Result<HttpResponse> {
+
+    check_admin(&req)?;
+
+    let days = query.days.unwrap_or(7).clamp(1, MAX_REPORT_DAYS);
+    let entries = log.entries(days).await
+        .map_err(|e| ErrorInternalServerError(e))?;
+
+    let res = HttpResponse::Ok()
+        .json(report(&entries, query.limit.unwrap_or(20)));
+
+    Ok(res)
+}
\ No newline at end of file
@@ -0,0 +1,50 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::delete;
+use crate::admin::{check_admin, publish_index_delete};
+use crate::store::{delete_subtitle_object, remove_subtitle};
+
+// drops one subtitle track of the video, e.g. a bad translation. it can be requested again
+// through `/api/video/subtitle`, which indexes the new track.
+#[delete("/api/admin/video/{id}/subtitle/{lang}")]
+pub async fn handler(req: HttpRequest, path: web::Path<(String, String)>) -> actix_web::Result<HttpResponse> {
+
+    check_admin(&req)?;
+
+    let (id, lang) = path.into_inner();
+    if lang.is_empty() || !lang.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(ErrorBadRequest(format!("invalid language: {}", lang)));
+    }
+
+    // the catalogue goes first, so the player never lists a track whose file is already gone.
+    let listed = remove_subtitle(&id, &lang).await
+        .map_err(|e| ErrorInternalServerError(e))?;
+
+    delete_subtitle_object(&id, &lang).await
+        .map_err(|e| ErrorInternalServerError(e))?;
+
+    publish_index_delete(&id, Some(&lang)).await
+        .map_err(|e| ErrorInternalServerError(e))?;
+
+    println!("removed subtitle {} of video {}, listed: {}", lang, id, listed);
+
+    Ok(HttpResponse::NoContent().finish())
+}
\ No newline at end of file
@@ -0,0 +1,51 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::delete;
+use crate::admin::{check_admin, publish_index_delete};
+use crate::store::{delete_video, delete_video_objects, find_video};
+
+// removes the video from the indexes and the catalogue. every step tolerates what an earlier,
+// partly failed request already removed, so the request can simply be repeated.
+#[delete("/api/admin/video/{id}")]
+pub async fn handler(req: HttpRequest, id: web::Path<String>) -> actix_web::Result<HttpResponse> {
+
+    check_admin(&req)?;
+
+    let id = id.into_inner();
+
+    let video = find_video(&id).await
+        .map_err(|e| ErrorInternalServerError(e))?;
+
+    publish_index_delete(&id, None).await
+        .map_err(|e| ErrorInternalServerError(e))?;
+
+    let thumbnail_key = video.as_ref().and_then(|v| v.thumbnail_key.as_deref());
+    let deleted = delete_video_objects(&id, thumbnail_key).await
+        .map_err(|e| ErrorInternalServerError(e))?;
+
+    // the item goes last, its stream record removes the video from the video index.
+    delete_video(&id).await
+        .map_err(|e| ErrorInternalServerError(e))?;
+
+    println!("deleted video {}: {:?}", id, deleted);
+
+    Ok(HttpResponse::NoContent().finish())
+}
\ No newline at end of file
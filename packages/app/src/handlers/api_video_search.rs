@@ -16,12 +16,16 @@
  * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
  
+use std::time::Instant;
 use actix_web::{HttpResponse, web};
-use actix_web::error::{ ErrorInternalServerError};
 use actix_web::get;
-use aws_sdk_lambda::types::Blob;
 use serde::{Deserialize};
-use serde_json::{json, Value};
+use lib::search_event::TantivySearchEvent;
+use crate::analytics::{record, SearchLog, SearchLogEntry};
+use crate::search::search_subtitle;
+
+// clients report opened results to `/api/search/click` with this id.
+const SEARCH_ID_HEADER: &str = "X-Search-Id";
 
 #[derive(Deserialize)]
 pub struct VideoSearchRequest {
@@ -29,38 +33,49 @@ pub struct VideoSearchRequest {
     query: String,
     video_id: Option<String>,
     lang: String,
+    #[serde(default)]
+    fuzzy: bool,
+    distance: Option<u8>,
+    #[serde(default)]
+    cross_lingual: bool,
+    #[serde(default)]
+    translate: bool,
+    #[serde(default)]
+    semantic: bool,
+    context: Option<usize>,
 }
 
 #[get("/api/video/search")]
-pub async fn handler(req: web::Query<VideoSearchRequest>) -> actix_web::Result<HttpResponse> {
-
-    let tantivy_function_name = dotenv::var("TANTIVY_SEARCH_FUNCTION_NAME")
-        .expect("TANTIVY_SEARCH_FUNCTION_NAME must be set.");
-    let shared_config = aws_config::from_env().load().await;
-
-    let lambda = aws_sdk_lambda::Client::new(&shared_config);
+pub async fn handler(log: web::Data<SearchLog>, req: web::Query<VideoSearchRequest>) -> actix_web::Result<HttpResponse> {
 
-    let mut query = format!("({})", req.query);
-    if let Some(video_id) = req.video_id.as_ref() {
-        query.push_str(" AND ");
-        query.push_str(video_id);
-    }
-    let payload = serde_json::to_string(&json!({
-        "lang": req.lang,
-        "query": query
-    })).unwrap();
-    let output = lambda.invoke()
-        .function_name(tantivy_function_name)
-        .payload(Blob::new(payload))
-        .send()
-        .await
-        .map_err(ErrorInternalServerError)?;
+    let req = req.into_inner();
+    let started = Instant::now();
+    let hits = search_subtitle(&TantivySearchEvent {
+        lang: req.lang.clone(),
+        query: req.query.clone(),
+        video_id: req.video_id.clone(),
+        fuzzy: req.fuzzy,
+        fuzzy_distance: req.distance,
+        cross_lingual: req.cross_lingual,
+        translate: req.translate,
+        semantic: req.semantic,
+        context: req.context,
+        ..Default::default()
+    }).await?;
 
-    let bytes = output.payload.unwrap().into_inner();
-    let value = serde_json::from_slice::<Value>(&bytes).unwrap();
+    let entry = SearchLogEntry::search(
+        &req.lang,
+        &req.query,
+        req.video_id.as_deref(),
+        hits.len(),
+        started.elapsed().as_millis() as u64);
+    let search_id = entry.search_id.clone();
+    // the search is answered even if it can't be recorded.
+    record(&log, entry);
 
     let res = HttpResponse::Ok()
-        .json(value);
+        .insert_header((SEARCH_ID_HEADER, search_id))
+        .json(hits);
 
     Ok(res)
 }
\ No newline at end of file
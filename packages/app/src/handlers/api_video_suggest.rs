@@ -0,0 +1,43 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use actix_web::{HttpResponse, web};
+use actix_web::get;
+use serde::{Deserialize};
+use crate::search::suggest;
+
+#[derive(Deserialize)]
+pub struct VideoSuggestRequest {
+    #[serde(rename = "q")]
+    query: String,
+    lang: String,
+    video_id: Option<String>,
+    limit: Option<usize>,
+}
+
+#[get("/api/video/suggest")]
+pub async fn handler(req: web::Query<VideoSuggestRequest>) -> actix_web::Result<HttpResponse> {
+
+    let req = req.into_inner();
+    let suggestions = suggest(&req.query, &req.lang, req.video_id, req.limit).await?;
+
+    let res = HttpResponse::Ok()
+        .json(suggestions);
+
+    Ok(res)
+}
\ No newline at end of file
@@ -19,4 +19,13 @@
 pub mod index;
 pub mod video_detail;
 pub mod api_video_search;
-pub mod api_request_subtitle;
\ No newline at end of file
+pub mod api_request_subtitle;
+pub mod search;
+pub mod api_search;
+pub mod api_video_suggest;
+pub mod api_index_stats;
+pub mod api_video_delete;
+pub mod api_subtitle_delete;
+pub mod api_search_click;
+pub mod api_search_report;
+pub mod api_search_facets;
\ No newline at end of file
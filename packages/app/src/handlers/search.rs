@@ -0,0 +1,143 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use actix_web::error::ErrorInternalServerError;
+use actix_web::{HttpResponse, web};
+use actix_web::get;
+use actix_web::http::header;
+use askama::Template;
+use serde::{Deserialize};
+use lib::search_event::TantivySearchEvent;
+use crate::search::{facet_counts, search_library, split_facets, FacetCount, SearchError, VideoHits};
+
+// facets offered to narrow the results. the language is chosen by the form already.
+const FACET_GROUPS: [(&str, &str); 2] = [("speaker", "Speaker"), ("uploaded", "Uploaded")];
+
+#[derive(Template)]
+#[template(path = "search.html")]
+struct SearchTemplate {
+    query: String,
+    lang: String,
+    fuzzy: bool,
+    facets: String,
+    facet_groups: Vec<FacetGroup>,
+    results: Vec<VideoHits>,
+    error: Option<String>,
+}
+
+struct FacetGroup {
+    title: String,
+    links: Vec<FacetLink>,
+}
+
+// a link toggles its facet in the current filters.
+struct FacetLink {
+    label: String,
+    count: u64,
+    active: bool,
+    href: String,
+}
+
+#[derive(Deserialize)]
+pub struct SearchPageQuery {
+    #[serde(rename = "q")]
+    query: Option<String>,
+    lang: Option<String>,
+    #[serde(default)]
+    fuzzy: bool,
+    facets: Option<String>,
+}
+
+#[get("/search")]
+pub async fn handler(query: web::Query<SearchPageQuery>) -> actix_web::Result<HttpResponse> {
+
+    let query = query.into_inner();
+    let q = query.query.unwrap_or_default();
+    let lang = query.lang.unwrap_or_else(|| "en".to_string());
+    let facets = split_facets(query.facets.as_deref());
+
+    let (results, facet_groups, error) = if q.trim().is_empty() {
+        (vec![], vec![], None)
+    } else {
+        let req = TantivySearchEvent {
+            lang: lang.clone(),
+            query: q.clone(),
+            fuzzy: query.fuzzy,
+            context: Some(1),
+            facets: facets.clone(),
+            ..Default::default()
+        };
+        let counts = facet_counts(&req).await;
+        match search_library(req, 3).await {
+            Ok(results) => {
+                // the results are still useful without the facets.
+                let counts = counts.unwrap_or_else(|e| {
+                    println!("failed to count facets: {}", e);
+                    vec![]
+                });
+                let base = format!("/search?q={}&lang={}&fuzzy={}",
+                                   url_escape::encode_component(&q), url_escape::encode_component(&lang), query.fuzzy);
+                let groups = FACET_GROUPS.iter()
+                    .map(|(name, title)| facet_group(name, title, &counts, &facets, &base))
+                    .filter(|g| !g.links.is_empty())
+                    .collect();
+                (results, groups, None)
+            }
+            Err(SearchError::InvalidQuery(reason)) => (vec![], vec![], Some(reason)),
+            Err(e @ SearchError::Syntax(_)) => (vec![], vec![], Some(e.to_string())),
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    let html = SearchTemplate { query: q, lang, fuzzy: query.fuzzy, facets: facets.join(","), facet_groups, results, error }
+        .render()
+        .map_err(|e| ErrorInternalServerError(e))?;
+
+    let response = HttpResponse::Ok()
+        .content_type(header::ContentType::html())
+        .body(html);
+
+    Ok(response)
+}
+
+fn facet_group(name: &str, title: &str, counts: &[FacetCount], active: &[String], base: &str) -> FacetGroup {
+    let prefix = format!("/{}/", name);
+    let links = counts.iter()
+        .filter_map(|c| c.facet.strip_prefix(&prefix).map(|label| (label, c)))
+        .map(|(label, c)| {
+            let is_active = active.contains(&c.facet);
+            let toggled = if is_active {
+                active.iter().filter(|f| **f != c.facet).cloned().collect::<Vec<_>>()
+            } else {
+                active.iter().cloned().chain([c.facet.clone()]).collect::<Vec<_>>()
+            };
+            FacetLink {
+                label: label.to_string(),
+                count: c.count,
+                active: is_active,
+                href: format!("{}&facets={}", base, url_escape::encode_component(&toggled.join(","))),
+            }
+        })
+        .collect();
+
+    FacetGroup { title: title.to_string(), links }
+}
+
+mod filters {
+    pub use crate::askama_filters::*;
+}
\ No newline at end of file
@@ -16,13 +16,17 @@
  * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
  
+use std::collections::HashMap;
 use actix_web::error::{ErrorInternalServerError, ErrorNotFound};
 use actix_web::{HttpResponse, web};
 use actix_web::get;
 use actix_web::http::header::{self};
 use askama::Template;
 use serde::{Deserialize};
-use crate::store::{get_video, VideoItem};
+use crate::search::related_videos;
+use crate::store::{batch_get_videos, get_video, VideoItem};
+
+const RELATED_VIDEO_LIMIT: usize = 4;
 
 #[derive(Template)]
 #[template(path = "video-detail.html")]
@@ -31,14 +35,16 @@ struct VideoDetailTemplate {
     video: VideoItem,
     content_host: String,
     timing: f32,
+    related: Vec<VideoItem>,
 }
 
 impl VideoDetailTemplate {
-    pub fn new(video: VideoItem, timing: Option<f32>) -> Self {
+    pub fn new(video: VideoItem, timing: Option<f32>, related: Vec<VideoItem>) -> Self {
         VideoDetailTemplate {
             video,
             content_host: dotenv::var("CONTENT_HOST").expect("CONTENT_HOST must be set"),
-            timing: timing.unwrap_or(0.01)
+            timing: timing.unwrap_or(0.01),
+            related,
         }
     }
 }
@@ -57,7 +63,9 @@ pub async fn handler(id: web::Path<String>, query: web::Query<VideoDetailQuery>)
     let item = get_video(&id).await
         .map_err(|e| ErrorNotFound(e))?;
 
-    let html = VideoDetailTemplate::new(item, query.timing)
+    let related = load_related_videos(&item).await;
+
+    let html = VideoDetailTemplate::new(item, query.timing, related)
         .render()
         .map_err(|e| ErrorInternalServerError(e))?;
 
@@ -68,6 +76,41 @@ pub async fn handler(id: web::Path<String>, query: web::Query<VideoDetailQuery>)
     Ok(response)
 }
 
+// related videos are optional on the page, so failures only leave the strip empty.
+async fn load_related_videos(video: &VideoItem) -> Vec<VideoItem> {
+    let content_language = video.lang.split('-').next().unwrap_or_default();
+    let lang = if video.subtitles.iter().any(|l| l == content_language) {
+        content_language
+    } else if let Some(lang) = video.subtitles.first() {
+        lang.as_str()
+    } else {
+        return vec![];
+    };
+
+    let ids = match related_videos(&video.id, lang, RELATED_VIDEO_LIMIT).await {
+        Ok(ids) => ids,
+        Err(e) => {
+            println!("failed to search related videos of {}: {}", video.id, e);
+            return vec![];
+        }
+    };
+
+    // one catalogue request for every related video, kept in the order of the ranking.
+    let mut videos = match batch_get_videos(&ids).await {
+        Ok(videos) => videos.into_iter()
+            .map(|video| (video.id.clone(), video))
+            .collect::<HashMap<_, _>>(),
+        Err(e) => {
+            println!("failed to load related videos of {}: {}", video.id, e);
+            return vec![];
+        }
+    };
+
+    ids.iter()
+        .filter_map(|id| videos.remove(id))
+        .collect()
+}
+
 mod filters {
     pub use crate::askama_filters::*;
 }
\ No newline at end of file
@@ -16,27 +16,49 @@
  * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
  
-use actix_web::{App, HttpServer};
+use actix_web::{App, HttpServer, web};
 use actix_web::middleware::Logger;
 use env_logger::Env;
 use lambda_web::{is_running_on_lambda, LambdaError, run_actix_on_lambda};
+use crate::analytics::SearchLog;
 
 pub mod handlers;
 pub mod store;
+pub mod search;
+pub mod search_backend;
 pub mod askama_filters;
+pub mod admin;
+pub mod analytics;
+pub mod query;
+
+// the JSON bodies are small, a click or a subtitle request.
+const JSON_LIMIT: usize = 4096;
 
 #[actix_web::main]
 async fn main() -> Result<(), LambdaError> {
 
     env_logger::init_from_env(Env::default().default_filter_or("info"));
 
+    let search_log = web::Data::new(SearchLog::from_env().await);
+
     let factory = move || {
         App::new()
             .wrap(Logger::default())
+            .app_data(search_log.clone())
+            .app_data(web::JsonConfig::default().limit(JSON_LIMIT))
             .service(handlers::index::handler)
             .service(handlers::video_detail::handler)
             .service(handlers::api_video_search::handler)
             .service(handlers::api_request_subtitle::handler)
+            .service(handlers::search::handler)
+            .service(handlers::api_search::handler)
+            .service(handlers::api_search_facets::handler)
+            .service(handlers::api_video_suggest::handler)
+            .service(handlers::api_index_stats::handler)
+            .service(handlers::api_video_delete::handler)
+            .service(handlers::api_subtitle_delete::handler)
+            .service(handlers::api_search_click::handler)
+            .service(handlers::api_search_report::handler)
     };
 
     if is_running_on_lambda() {
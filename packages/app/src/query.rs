@@ -0,0 +1,280 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+ 
+use std::fmt;
+
+// a search query of the search box, e.g. `speaker:2 after:5m "free tier" -lambda`.
+// `speaker:{n}` is the transcription label `spk_{n}`, times are like `90`, `90s`, `5m`, `1h30m` or `1:30`.
+// anything else is searched as it is typed: unknown `name:value` words are terms, and a stray `-`
+// or `"` is ignored. only the filters above fail on invalid values.
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedQuery {
+    pub terms: Vec<String>,
+    pub phrases: Vec<String>,
+    // negated words and phrases, e.g. `-lambda` or `-"free tier"`.
+    pub exclude: Vec<String>,
+    pub speakers: Vec<String>,
+    // start time range of the cues in seconds.
+    pub after: Option<u32>,
+    pub before: Option<u32>,
+}
+
+impl ParsedQuery {
+    // the words and phrases to search for, in the syntax of the search function.
+    pub fn search_text(&self) -> String {
+        self.terms.iter()
+            .cloned()
+            .chain(self.phrases.iter().map(|p| format!("\"{}\"", p)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    // 1 based character position in the query.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+const FILTERS: [&str; 3] = ["speaker", "after", "before"];
+
+fn error(column: usize, message: String) -> QueryError {
+    QueryError { column, message }
+}
+
+pub fn parse_query(text: &str) -> Result<ParsedQuery, QueryError> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut query = ParsedQuery::default();
+    let mut before_column = 0;
+
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let negated = chars[i] == '-';
+        if negated {
+            i += 1;
+            if i == chars.len() || chars[i].is_whitespace() {
+                continue;
+            }
+        }
+
+        if chars[i] == '"' {
+            // the words after a quote that is never closed are searched on their own.
+            let end = match chars[i + 1..].iter().position(|c| *c == '"') {
+                Some(p) => i + 1 + p,
+                None => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let phrase = chars[i + 1..end].iter().collect::<String>();
+            let phrase = phrase.split_whitespace().collect::<Vec<_>>().join(" ");
+            i = end + 1;
+            if phrase.is_empty() {
+                continue;
+            }
+            if negated {
+                query.exclude.push(phrase);
+            } else {
+                query.phrases.push(phrase);
+            }
+            continue;
+        }
+
+        let end = chars[i..].iter().position(|c| c.is_whitespace() || *c == '"')
+            .map(|p| i + p)
+            .unwrap_or(chars.len());
+        let word = chars[i..end].iter().collect::<String>();
+        let word_column = i + 1;
+        i = end;
+
+        let (name, value) = match word.split_once(':') {
+            Some((name, value)) if FILTERS.contains(&name) => (name, value),
+            _ => {
+                if negated {
+                    query.exclude.push(word);
+                } else {
+                    query.terms.push(word);
+                }
+                continue;
+            }
+        };
+        if negated {
+            return Err(error(start + 1, format!("filter `{}` can't be negated", name)));
+        }
+        let value_column = word_column + name.chars().count() + 1;
+        match name {
+            "speaker" => {
+                let speaker = parse_speaker(value)
+                    .ok_or_else(|| error(value_column, format!("invalid speaker `{}`, e.g. speaker:1", value)))?;
+                query.speakers.push(speaker);
+            }
+            "after" | "before" => {
+                let seconds = parse_time(value)
+                    .ok_or_else(|| error(value_column, format!("invalid time `{}`, e.g. 90s, 5m, 1h30m or 1:30", value)))?;
+                let bound = if name == "after" { &mut query.after } else { &mut query.before };
+                if bound.replace(seconds).is_some() {
+                    return Err(error(word_column, format!("`{}` is given twice", name)));
+                }
+                if name == "before" {
+                    before_column = value_column;
+                }
+            }
+            _ => unreachable!("{} is not a filter", name),
+        }
+    }
+
+    if let (Some(after), Some(before)) = (query.after, query.before) {
+        if after >= before {
+            return Err(error(before_column, "`before` has to be later than `after`".to_string()));
+        }
+    }
+    if query.terms.is_empty() && query.phrases.is_empty() {
+        return Err(error(chars.len() + 1, "query needs a word or phrase to search for".to_string()));
+    }
+
+    Ok(query)
+}
+
+// `2` and `spk_2` are the same speaker.
+fn parse_speaker(value: &str) -> Option<String> {
+    let number = value.strip_prefix("spk_").unwrap_or(value);
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(format!("spk_{}", number))
+}
+
+// seconds of `90`, `90s`, `5m`, `1h30m`, `1:30` or `1:02:03`.
+fn parse_time(value: &str) -> Option<u32> {
+    if value.contains(':') {
+        let parts = value.split(':')
+            .map(|p| p.parse::<u32>().ok())
+            .collect::<Option<Vec<_>>>()?;
+        if parts.len() > 3 {
+            return None;
+        }
+        return parts.into_iter().try_fold(0u32, |t, p| t.checked_mul(60)?.checked_add(p));
+    }
+    if value.is_empty() {
+        return None;
+    }
+
+    let mut seconds = 0u32;
+    let mut number = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        seconds = seconds.checked_add(number.parse::<u32>().ok()?.checked_mul(unit)?)?;
+        number.clear();
+    }
+    if !number.is_empty() {
+        seconds = seconds.checked_add(number.parse::<u32>().ok()?)?;
+    }
+
+    Some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::{parse_query, parse_time, ParsedQuery};
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_query_test() {
+        let query = parse_query("free  tier \"lambda   layers\"").unwrap();
+        assert_eq!(query.terms, strings(&["free", "tier"]));
+        assert_eq!(query.phrases, strings(&["lambda layers"]));
+        assert_eq!(query.search_text(), "free tier \"lambda layers\"");
+
+        let query = parse_query("-lambda aws -\"free tier\"").unwrap();
+        assert_eq!(query.terms, strings(&["aws"]));
+        assert_eq!(query.exclude, strings(&["lambda", "free tier"]));
+
+        assert_eq!(parse_query("speaker:2 speaker:spk_3 after:5m before:1:30:00 aws").unwrap(), ParsedQuery {
+            terms: strings(&["aws"]),
+            speakers: strings(&["spk_2", "spk_3"]),
+            after: Some(300),
+            before: Some(5400),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn parse_query_plain_text_test() {
+        // unknown filters, stray dashes and quotes are part of the searched text.
+        assert_eq!(parse_query("std::fs https://aws.amazon.com").unwrap().terms, strings(&["std::fs", "https://aws.amazon.com"]));
+        assert_eq!(parse_query("\"free tier").unwrap().terms, strings(&["free", "tier"]));
+        assert_eq!(parse_query("a - b \"\"").unwrap().terms, strings(&["a", "b"]));
+        assert_eq!(parse_query("-foo:1 aws").unwrap().exclude, strings(&["foo:1"]));
+    }
+
+    #[test]
+    fn parse_query_error_test() {
+        let column = |text: &str| parse_query(text).unwrap_err().column;
+
+        assert_eq!(column("a speaker:x"), 11);
+        assert_eq!(column("a after:5x"), 9);
+        assert_eq!(column("a after:1m after:2m"), 12);
+        assert_eq!(column("a after:10m before:5m"), 20);
+        assert_eq!(column("a -speaker:1"), 3);
+        assert_eq!(column("-a"), 3);
+        assert_eq!(column(""), 1);
+        assert_eq!(parse_query("a after:5x").unwrap_err().to_string(),
+                   "invalid time `5x`, e.g. 90s, 5m, 1h30m or 1:30 at column 9");
+    }
+
+    #[test]
+    fn parse_time_test() {
+        assert_eq!(parse_time("90"), Some(90));
+        assert_eq!(parse_time("90s"), Some(90));
+        assert_eq!(parse_time("5m"), Some(300));
+        assert_eq!(parse_time("1h30m"), Some(5400));
+        assert_eq!(parse_time("1:30"), Some(90));
+        assert_eq!(parse_time("1:02:03"), Some(3723));
+        assert_eq!(parse_time(""), None);
+        assert_eq!(parse_time("5x"), None);
+        assert_eq!(parse_time("1:2:3:4"), None);
+        assert_eq!(parse_time("99999999h"), None);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,338 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::collections::HashMap;
+use std::fmt;
+use actix_web::{HttpResponse, ResponseError};
+use actix_web::http::StatusCode;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use lib::search_event::{SearchAction, TantivySearchEvent};
+use crate::query::{parse_query, QueryError};
+use crate::search_backend::SearchBackend;
+use crate::store::{batch_get_videos, VideoItem};
+
+const LIBRARY_SEARCH_LIMIT: usize = 100;
+
+#[derive(Debug)]
+pub enum SearchError {
+    InvalidQuery(String),
+    Syntax(QueryError),
+    Internal(anyhow::Error),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::InvalidQuery(reason) => write!(f, "{}", reason),
+            SearchError::Syntax(e) => write!(f, "{}", e),
+            SearchError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl ResponseError for SearchError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SearchError::InvalidQuery(_) | SearchError::Syntax(_) => StatusCode::BAD_REQUEST,
+            SearchError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            SearchError::Syntax(e) => json!({ "message": self.to_string(), "column": e.column }),
+            _ => json!({ "message": self.to_string() }),
+        };
+        HttpResponse::build(self.status_code())
+            .json(body)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ContextCue {
+    pub time: String,
+    pub body: String,
+}
+
+#[derive(Serialize)]
+pub struct SubtitleHit {
+    pub video_id: String,
+    pub time: String,
+    pub seconds: u32,
+    pub body: String,
+    pub score: f32,
+    pub before: Vec<ContextCue>,
+    pub after: Vec<ContextCue>,
+}
+
+impl SubtitleHit {
+    pub fn from_value(value: &Value) -> Option<SubtitleHit> {
+        // stored fields are serialized by tantivy as arrays, e.g. {"time": ["00:01:02.000"]}
+        let field = |name: &str| value.get(name)
+            .and_then(|v| v.get(0))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let context = |name: &str| value.get(name)
+            .and_then(|v| serde_json::from_value::<Vec<ContextCue>>(v.clone()).ok())
+            .unwrap_or_default();
+
+        let time = field("time")?;
+        Some(SubtitleHit {
+            video_id: field("video_id")?,
+            seconds: time_to_seconds(&time),
+            time,
+            body: field("body")?,
+            score: hit_score(value),
+            before: context("before"),
+            after: context("after"),
+        })
+    }
+}
+
+fn hit_score(value: &Value) -> f32 {
+    value.get("score").and_then(|v| v.as_f64()).unwrap_or_default() as f32
+}
+
+// video documents match on title or keywords and have no cue time.
+fn title_hit(value: &Value) -> Option<(String, f32)> {
+    if value.get("title").is_none() {
+        return None;
+    }
+    let video_id = value.get("video_id")
+        .and_then(|v| v.get(0))
+        .and_then(|v| v.as_str())?;
+
+    Some((video_id.to_string(), hit_score(value)))
+}
+
+#[derive(Serialize)]
+pub struct VideoHits {
+    pub video: VideoItem,
+    pub score: f32,
+    pub moments: Vec<SubtitleHit>,
+}
+
+struct VideoGroup {
+    video_id: String,
+    score: f32,
+    moments: Vec<SubtitleHit>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Suggestion {
+    pub text: String,
+    pub count: u64,
+}
+
+pub async fn search_subtitle(req: &TantivySearchEvent) -> Result<Vec<Value>, SearchError> {
+    if req.query.trim().is_empty() {
+        return Err(SearchError::InvalidQuery("query is empty".to_string()));
+    }
+
+    invoke_search_function(&with_parsed_query(req)?).await
+}
+
+// moves the filters of the query language into the request, the search function gets the words and phrases only.
+fn with_parsed_query(req: &TantivySearchEvent) -> Result<TantivySearchEvent, SearchError> {
+    let parsed = parse_query(&req.query).map_err(SearchError::Syntax)?;
+
+    let mut facets = req.facets.clone();
+    facets.extend(parsed.speakers.iter().map(|speaker| format!("/speaker/{}", speaker)));
+    Ok(TantivySearchEvent {
+        query: parsed.search_text(),
+        facets,
+        exclude: parsed.exclude,
+        after: parsed.after,
+        before: parsed.before,
+        ..req.clone()
+    })
+}
+
+pub async fn suggest(query: &str, lang: &str, video_id: Option<String>, limit: Option<usize>) -> Result<Vec<Suggestion>, SearchError> {
+    if query.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    let values = invoke_search_function(&TantivySearchEvent {
+        action: SearchAction::Suggest,
+        lang: lang.to_string(),
+        query: query.to_string(),
+        video_id,
+        limit,
+        ..Default::default()
+    }).await?;
+
+    let suggestions = values.into_iter()
+        .filter_map(|v| serde_json::from_value::<Suggestion>(v).ok())
+        .collect();
+
+    Ok(suggestions)
+}
+
+pub async fn related_videos(video_id: &str, lang: &str, limit: usize) -> Result<Vec<String>, SearchError> {
+    let values = invoke_search_function(&TantivySearchEvent {
+        action: SearchAction::Related,
+        lang: lang.to_string(),
+        video_id: Some(video_id.to_string()),
+        limit: Some(limit),
+        ..Default::default()
+    }).await?;
+
+    let ids = values.iter()
+        .filter_map(|v| v.get("video_id").and_then(|id| id.as_str()))
+        .map(|id| id.to_string())
+        .collect();
+
+    Ok(ids)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FacetCount {
+    pub facet: String,
+    pub count: u64,
+}
+
+// counts of the facets of the cues matching the query and facet filters of the request.
+pub async fn facet_counts(req: &TantivySearchEvent) -> Result<Vec<FacetCount>, SearchError> {
+    if req.query.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    let values = invoke_search_function(&with_parsed_query(&TantivySearchEvent {
+        action: SearchAction::Facets,
+        lang: req.lang.clone(),
+        query: req.query.clone(),
+        video_id: req.video_id.clone(),
+        fuzzy: req.fuzzy,
+        fuzzy_distance: req.fuzzy_distance,
+        facets: req.facets.clone(),
+        ..Default::default()
+    })?).await?;
+
+    let counts = values.into_iter()
+        .filter_map(|v| serde_json::from_value::<FacetCount>(v).ok())
+        .collect();
+
+    Ok(counts)
+}
+
+// facet filters are given as one comma separated parameter.
+pub fn split_facets(facets: Option<&str>) -> Vec<String> {
+    facets.unwrap_or_default()
+        .split(',')
+        .map(|f| f.trim())
+        .filter(|f| !f.is_empty())
+        .map(|f| f.to_string())
+        .collect()
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IndexStats {
+    pub name: String,
+    pub segments: usize,
+    pub docs: u64,
+    pub deleted_docs: u64,
+    pub size_bytes: u64,
+}
+
+pub async fn index_stats() -> Result<Vec<IndexStats>, SearchError> {
+    let values = invoke_search_function(&TantivySearchEvent {
+        action: SearchAction::Stats,
+        ..Default::default()
+    }).await?;
+
+    let stats = values.into_iter()
+        .filter_map(|v| serde_json::from_value::<IndexStats>(v).ok())
+        .collect();
+
+    Ok(stats)
+}
+
+async fn invoke_search_function(req: &TantivySearchEvent) -> Result<Vec<Value>, SearchError> {
+    SearchBackend::get().search(req).await
+}
+
+pub async fn search_library(mut req: TantivySearchEvent, moments_per_video: usize) -> Result<Vec<VideoHits>, SearchError> {
+    req.video_id = None;
+    req.limit = Some(LIBRARY_SEARCH_LIMIT);
+    req.include_videos = true;
+    let values = search_subtitle(&req).await?;
+
+    // a video ranks by its best cue plus the (boosted) score of its title and keyword match.
+    let mut groups: Vec<VideoGroup> = vec![];
+    for value in values.iter() {
+        let (video_id, score, hit) = if let Some(hit) = SubtitleHit::from_value(value) {
+            (hit.video_id.clone(), hit.score, Some(hit))
+        } else if let Some((video_id, score)) = title_hit(value) {
+            (video_id, score, None)
+        } else {
+            continue;
+        };
+
+        let index = match groups.iter().position(|g| g.video_id == video_id) {
+            Some(index) => index,
+            None => {
+                groups.push(VideoGroup { video_id, score: 0f32, moments: vec![] });
+                groups.len() - 1
+            }
+        };
+        let group = &mut groups[index];
+        match hit {
+            Some(hit) => {
+                // hits are ordered by score, so the first cue of each video is its best one.
+                if group.moments.is_empty() {
+                    group.score += score;
+                }
+                if group.moments.len() < moments_per_video {
+                    group.moments.push(hit);
+                }
+            }
+            None => group.score += score,
+        }
+    }
+    groups.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let ids = groups.iter().map(|g| g.video_id.clone()).collect::<Vec<_>>();
+    let mut videos = batch_get_videos(&ids).await
+        .map_err(SearchError::Internal)?
+        .into_iter()
+        .map(|video| (video.id.clone(), video))
+        .collect::<HashMap<_, _>>();
+
+    let results = groups.into_iter()
+        .filter_map(|group| {
+            videos.remove(&group.video_id).map(|video| VideoHits {
+                video,
+                score: group.score,
+                moments: group.moments,
+            })
+        })
+        .collect();
+
+    Ok(results)
+}
+
+fn time_to_seconds(time: &str) -> u32 {
+    let mut parts = time.split(|c| c == ':' || c == '.');
+    let mut next = || parts.next().and_then(|s| s.parse::<u32>().ok()).unwrap_or(0);
+    let (hh, mm, ss) = (next(), next(), next());
+
+    hh * 3600 + mm * 60 + ss
+}
\ No newline at end of file
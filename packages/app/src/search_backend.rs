@@ -0,0 +1,134 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+ 
+use std::sync::OnceLock;
+use aws_sdk_lambda::types::Blob;
+#[cfg(feature = "local-search")]
+use lib::cache::IndexCache;
+use lib::search_event::TantivySearchEvent;
+#[cfg(feature = "local-search")]
+use lib::synonym::Synonyms;
+#[cfg(feature = "local-search")]
+use lib::translate::IdentityTranslator;
+#[cfg(feature = "local-search")]
+use lib::vector::Embedder;
+use serde_json::Value;
+use crate::search::SearchError;
+
+static SEARCH_BACKEND: OnceLock<SearchBackend> = OnceLock::new();
+
+// where searches run. `SEARCH_BACKEND=local` searches the indexes under `TANTIVY_MOUNT` within the app,
+// e.g. when running it without AWS, otherwise the search function is invoked. the local backend needs
+// the app to be built with the `local-search` feature.
+pub enum SearchBackend {
+    Lambda(String),
+    #[cfg(feature = "local-search")]
+    Local(LocalSearch),
+}
+
+// opened indexes are kept for the next requests, like in the search function.
+#[cfg(feature = "local-search")]
+pub struct LocalSearch {
+    indexes: IndexCache,
+    embedder: Option<Box<dyn Embedder>>,
+}
+
+impl SearchBackend {
+    pub fn from_env() -> Self {
+        match dotenv::var("SEARCH_BACKEND").as_deref() {
+            #[cfg(feature = "local-search")]
+            Ok("local") => {
+                let mount = dotenv::var("TANTIVY_MOUNT")
+                    .expect("TANTIVY_MOUNT must be set.");
+                SearchBackend::Local(LocalSearch {
+                    indexes: IndexCache::new(&mount),
+                    embedder: lib::vector::embedder_from_env(),
+                })
+            }
+            #[cfg(not(feature = "local-search"))]
+            Ok("local") => panic!("SEARCH_BACKEND=local needs the app to be built with the local-search feature."),
+            _ => {
+                let function_name = dotenv::var("TANTIVY_SEARCH_FUNCTION_NAME")
+                    .expect("TANTIVY_SEARCH_FUNCTION_NAME must be set.");
+                SearchBackend::Lambda(function_name)
+            }
+        }
+    }
+
+    pub fn get() -> &'static SearchBackend {
+        SEARCH_BACKEND.get_or_init(Self::from_env)
+    }
+
+    pub async fn search(&self, req: &TantivySearchEvent) -> Result<Vec<Value>, SearchError> {
+        match self {
+            SearchBackend::Lambda(function_name) => invoke_search_function(function_name, req).await,
+            #[cfg(feature = "local-search")]
+            SearchBackend::Local(local) => local.search(req).await,
+        }
+    }
+}
+
+#[cfg(feature = "local-search")]
+impl LocalSearch {
+    // synonyms live in the bucket and queries aren't translated, so neither is applied locally.
+    async fn search(&self, req: &TantivySearchEvent) -> Result<Vec<Value>, SearchError> {
+        lib::search::execute(&self.indexes, req, &Synonyms::default(), &IdentityTranslator, self.embedder.as_deref())
+            .await
+            .map_err(|e| match e {
+                lib::search::SearchError::InvalidQuery(reason) => SearchError::InvalidQuery(reason),
+                lib::search::SearchError::Index(e) => SearchError::Internal(e),
+            })
+    }
+}
+
+async fn invoke_search_function(function_name: &str, req: &TantivySearchEvent) -> Result<Vec<Value>, SearchError> {
+    let shared_config = aws_config::from_env().load().await;
+
+    let lambda = aws_sdk_lambda::Client::new(&shared_config);
+
+    let payload = serde_json::to_string(req).unwrap();
+    let output = lambda.invoke()
+        .function_name(function_name)
+        .payload(Blob::new(payload))
+        .send()
+        .await
+        .map_err(|e| SearchError::Internal(e.into()))?;
+
+    let function_error = output.function_error().is_some();
+    let bytes = output.payload.unwrap().into_inner();
+    let value = serde_json::from_slice::<Value>(&bytes)
+        .map_err(|e| SearchError::Internal(e.into()))?;
+
+    if function_error {
+        // the search function reports unparseable user input as `InvalidQuery: <reason>`.
+        let message = value.get("errorMessage")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+
+        return Err(match message.strip_prefix("InvalidQuery: ") {
+            Some(reason) => SearchError::InvalidQuery(reason.to_string()),
+            None => SearchError::Internal(anyhow::anyhow!("{}", message)),
+        });
+    }
+
+    let hits = serde_json::from_value::<Vec<Value>>(value)
+        .map_err(|e| SearchError::Internal(e.into()))?;
+
+    Ok(hits)
+}
\ No newline at end of file
@@ -16,52 +16,54 @@
  * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
  
-use aws_sdk_dynamodb::model::AttributeValue;
-use serde_dynamo::{from_item, from_items};
-use serde::{Serialize, Deserialize};
+pub use lib::catalogue::VideoItem;
 
-#[derive(Serialize, Deserialize)]
-pub struct VideoItem {
-    pub id: String,
-    pub title: String,
-    pub lang: String,
-    pub subtitles: Vec<String>,
-    pub video_key: String,
-    pub thumbnail_key: Option<String>,
+pub async fn scan_videos() -> Result<Vec<VideoItem>, anyhow::Error> {
+    lib::catalogue::catalogue_from_env().await.scan_videos().await
 }
 
-pub async fn scan_videos() -> Result<Vec<VideoItem>, anyhow::Error> {
-    let table_name = dotenv::var("DYNAMODB_TABLE_NAME")
-        .expect("DYNAMODB_TABLE_NAME must be set.");
+pub async fn get_video(id: &str) -> Result<VideoItem, anyhow::Error> {
+    find_video(id).await?
+        .ok_or_else(|| anyhow::anyhow!("video {} not found", id))
+}
 
-    let shared_config = aws_config::from_env().load().await;
-    let dynamodb = aws_sdk_dynamodb::Client::new(&shared_config);
+pub async fn find_video(id: &str) -> Result<Option<VideoItem>, anyhow::Error> {
+    lib::catalogue::catalogue_from_env().await.find_video(id).await
+}
 
-    let output = dynamodb.scan()
-        .table_name(table_name)
-        .projection_expression("id, title, thumbnail_key, subtitles, lang, video_key")
-        .send()
-        .await?;
+// deleting a missing item succeeds, like the rest of the video deletion.
+pub async fn delete_video(id: &str) -> Result<(), anyhow::Error> {
+    lib::catalogue::catalogue_from_env().await.delete_video(id).await
+}
 
-    let items: Vec<VideoItem> = from_items(output.items.unwrap())?;
+// inverse of `add_subtitle` of the subtitle function. returns whether the language was listed.
+pub async fn remove_subtitle(id: &str, lang: &str) -> Result<bool, anyhow::Error> {
+    let catalogue = lib::catalogue::catalogue_from_env().await;
+    lib::catalogue::remove_subtitle(catalogue.as_ref(), id, lang).await
+}
 
-    Ok(items)
+pub async fn delete_subtitle_object(id: &str, lang: &str) -> Result<(), anyhow::Error> {
+    let objects = lib::storage::object_store_from_env().await;
+    objects.delete(&lib::subtitle::subtitle_key(id, lang)).await
 }
 
-pub async fn get_video(id: &str) -> Result<VideoItem, anyhow::Error> {
-    let table_name = dotenv::var("DYNAMODB_TABLE_NAME")
-        .expect("DYNAMODB_TABLE_NAME must be set.");
+// subtitles, transcription and thumbnail of the video. the uploaded source video is kept.
+pub async fn delete_video_objects(id: &str, thumbnail_key: Option<&str>) -> Result<Vec<String>, anyhow::Error> {
+    let objects = lib::storage::object_store_from_env().await;
 
-    let shared_config = aws_config::from_env().load().await;
-    let dynamodb = aws_sdk_dynamodb::Client::new(&shared_config);
+    let mut keys = vec![
+        format!("transcription/{}", id),
+        thumbnail_key.map(|k| k.to_string()).unwrap_or_else(|| lib::index::thumbnail_key(id)),
+    ];
+    keys.extend(objects.list(&format!("subtitle/{}/", id)).await?);
 
-    let output = dynamodb.get_item()
-        .table_name(table_name)
-        .key("id", AttributeValue::S(id.to_owned()))
-        .send()
-        .await?;
+    for key in keys.iter() {
+        objects.delete(key).await?;
+    }
 
-    let item = from_item(output.item.unwrap())?;
+    Ok(keys)
+}
 
-    Ok(item)
+pub async fn batch_get_videos(ids: &[String]) -> Result<Vec<VideoItem>, anyhow::Error> {
+    lib::catalogue::catalogue_from_env().await.batch_get_videos(ids).await
 }
\ No newline at end of file
@@ -21,6 +21,8 @@ import { IDistribution } from "aws-cdk-lib/aws-cloudfront";
 import { ITable } from "aws-cdk-lib/aws-dynamodb";
 import { IVpc } from "aws-cdk-lib/aws-ec2";
 import { Architecture, Code, IFunction } from "aws-cdk-lib/aws-lambda";
+import { IBucket } from "aws-cdk-lib/aws-s3";
+import { ITopic } from "aws-cdk-lib/aws-sns";
 import { IQueue } from "aws-cdk-lib/aws-sqs";
 import { Construct } from "constructs";
 import { RustLambdaFunction } from "./rust-lambda-function";
@@ -28,9 +30,14 @@ import { RustLambdaFunction } from "./rust-lambda-function";
 export interface AppFunctionProps {
   readonly vpc: IVpc;
   readonly dynamoDbTable: ITable;
+  readonly searchLogTable: ITable;
   readonly subtitleJobQueue: IQueue;
   readonly searchSubtitleFunction: IFunction;
   readonly distribution: IDistribution;
+  readonly bucket: IBucket;
+  readonly subtitleResultTopic: ITopic;
+  // enables the admin endpoints, e.g. `cdk deploy -c adminToken=<token>`.
+  readonly adminToken?: string;
 }
 
 export class AppFunction extends Construct {
@@ -41,9 +48,13 @@ export class AppFunction extends Construct {
     const {
       vpc,
       dynamoDbTable,
+      searchLogTable,
       subtitleJobQueue,
       searchSubtitleFunction,
       distribution,
+      bucket,
+      subtitleResultTopic,
+      adminToken,
     } = props;
 
     this.rustFunction = new RustLambdaFunction(this, "Function", {
@@ -54,12 +65,23 @@ export class AppFunction extends Construct {
       environment: {
         CONTENT_HOST: distribution.distributionDomainName,
         DYNAMODB_TABLE_NAME: dynamoDbTable.tableName,
+        ANALYTICS_TABLE_NAME: searchLogTable.tableName,
         TANTIVY_SEARCH_FUNCTION_NAME: searchSubtitleFunction.functionName,
         SUBTITLE_QUEUE_URL: subtitleJobQueue.queueUrl,
+        BUCKET_NAME: bucket.bucketName,
+        INDEX_TOPIC_ARN: subtitleResultTopic.topicArn,
+        ...(adminToken ? { ADMIN_TOKEN: adminToken } : {}),
       },
     });
     dynamoDbTable.grantReadWriteData(this.rustFunction.func);
+    searchLogTable.grantReadWriteData(this.rustFunction.func);
     subtitleJobQueue.grantSendMessages(this.rustFunction.func);
     searchSubtitleFunction.grantInvoke(this.rustFunction.func);
+    // video deletion
+    subtitleResultTopic.grantPublish(this.rustFunction.func);
+    bucket.grantRead(this.rustFunction.func, "subtitle/*");
+    for (const prefix of ["subtitle/*", "transcription/*", "thumbnail/*"]) {
+      bucket.grantDelete(this.rustFunction.func, prefix);
+    }
   }
 }
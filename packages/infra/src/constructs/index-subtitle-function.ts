@@ -20,8 +20,10 @@ import { Duration } from "aws-cdk-lib";
 import { IVpc } from "aws-cdk-lib/aws-ec2";
 import { IAccessPoint } from "aws-cdk-lib/aws-efs";
 import { Architecture, Code, FileSystem } from "aws-cdk-lib/aws-lambda";
-import { SnsEventSource } from "aws-cdk-lib/aws-lambda-event-sources";
+import { SqsEventSource } from "aws-cdk-lib/aws-lambda-event-sources";
 import { ITopic } from "aws-cdk-lib/aws-sns";
+import { SqsSubscription } from "aws-cdk-lib/aws-sns-subscriptions";
+import { Queue } from "aws-cdk-lib/aws-sqs";
 import { Construct } from "constructs";
 import { RustLambdaFunction } from "./rust-lambda-function";
 
@@ -33,6 +35,9 @@ export interface IndexSubtitleFunctionProps {
 
 export class IndexSubtitleFunction extends Construct {
   public readonly rustFunction: RustLambdaFunction;
+  public readonly queue: Queue;
+  public readonly dlq: Queue;
+
   constructor(scope: Construct, id: string, props: IndexSubtitleFunctionProps) {
     super(scope, id);
 
@@ -52,8 +57,26 @@ export class IndexSubtitleFunction extends Construct {
       filesystem: FileSystem.fromEfsAccessPoint(tantivyAccessPoint, mountPath),
     });
 
+    // index messages are queued, so a batch shares one index writer and failed messages are
+    // redelivered instead of being dropped.
+    this.dlq = new Queue(this, "Dlq");
+    this.queue = new Queue(this, "Queue", {
+      visibilityTimeout: Duration.seconds(1800),
+      deadLetterQueue: {
+        queue: this.dlq,
+        maxReceiveCount: 5,
+      },
+    });
+    subtitleResultTopic.addSubscription(
+      new SqsSubscription(this.queue, { rawMessageDelivery: true })
+    );
+
     this.rustFunction.func.addEventSource(
-      new SnsEventSource(subtitleResultTopic)
+      new SqsEventSource(this.queue, {
+        batchSize: 10,
+        maxBatchingWindow: Duration.seconds(10),
+        reportBatchItemFailures: true,
+      })
     );
   }
 }
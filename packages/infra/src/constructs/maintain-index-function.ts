@@ -0,0 +1,78 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+
+import { Duration } from "aws-cdk-lib";
+import { IVpc } from "aws-cdk-lib/aws-ec2";
+import { IAccessPoint } from "aws-cdk-lib/aws-efs";
+import { Rule, RuleTargetInput, Schedule } from "aws-cdk-lib/aws-events";
+import { LambdaFunction } from "aws-cdk-lib/aws-events-targets";
+import { Architecture, Code, FileSystem } from "aws-cdk-lib/aws-lambda";
+import { IBucket } from "aws-cdk-lib/aws-s3";
+import { Construct } from "constructs";
+import { RustLambdaFunction } from "./rust-lambda-function";
+
+export interface MaintainIndexFunctionProps {
+  readonly vpc: IVpc;
+  readonly tantivyAccessPoint: IAccessPoint;
+  readonly bucket: IBucket;
+}
+
+export class MaintainIndexFunction extends Construct {
+  public readonly rustFunction: RustLambdaFunction;
+  constructor(scope: Construct, id: string, props: MaintainIndexFunctionProps) {
+    super(scope, id);
+
+    const { vpc, tantivyAccessPoint, bucket } = props;
+
+    const mountPath = "/mnt/tantivy";
+
+    this.rustFunction = new RustLambdaFunction(this, "Function", {
+      vpc,
+      code: Code.fromAsset("../lambda/.dist/maintain_index/"),
+      architecture: Architecture.ARM_64,
+      environment: {
+        TANTIVY_MOUNT: mountPath,
+        BUCKET_NAME: bucket.bucketName,
+      },
+      timeout: Duration.minutes(15),
+      memorySize: 1024,
+      filesystem: FileSystem.fromEfsAccessPoint(tantivyAccessPoint, mountPath),
+    });
+
+    bucket.grantReadWrite(this.rustFunction.func, "snapshot/*");
+
+    // merges the segments left by incremental indexing once a day.
+    new Rule(this, "Schedule", {
+      schedule: Schedule.rate(Duration.days(1)),
+      targets: [
+        new LambdaFunction(this.rustFunction.func, {
+          event: RuleTargetInput.fromObject({ action: "optimize" }),
+        }),
+      ],
+    });
+
+    new Rule(this, "SnapshotSchedule", {
+      schedule: Schedule.rate(Duration.days(1)),
+      targets: [
+        new LambdaFunction(this.rustFunction.func, {
+          event: RuleTargetInput.fromObject({ action: "snapshot" }),
+        }),
+      ],
+    });
+  }
+}
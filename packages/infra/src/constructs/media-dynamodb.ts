@@ -16,11 +16,17 @@
  * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
 
-import { AttributeType, BillingMode, Table } from "aws-cdk-lib/aws-dynamodb";
+import {
+  AttributeType,
+  BillingMode,
+  StreamViewType,
+  Table,
+} from "aws-cdk-lib/aws-dynamodb";
 import { Construct } from "constructs";
 
 export class MediaDynamodb extends Construct {
   public readonly table: Table;
+  public readonly searchLogTable: Table;
 
   constructor(scope: Construct, id: string) {
     super(scope, id);
@@ -31,6 +37,21 @@ export class MediaDynamodb extends Construct {
         type: AttributeType.STRING,
       },
       billingMode: BillingMode.PAY_PER_REQUEST,
+      stream: StreamViewType.NEW_AND_OLD_IMAGES,
+    });
+
+    // append-only log of searches and clicked results, one partition per day.
+    this.searchLogTable = new Table(this, "SearchLogTable", {
+      partitionKey: {
+        name: "day",
+        type: AttributeType.STRING,
+      },
+      sortKey: {
+        name: "sk",
+        type: AttributeType.STRING,
+      },
+      billingMode: BillingMode.PAY_PER_REQUEST,
+      timeToLiveAttribute: "expires_at",
     });
   }
 }
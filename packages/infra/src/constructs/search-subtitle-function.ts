@@ -19,13 +19,16 @@
 import { Duration } from "aws-cdk-lib";
 import { IVpc } from "aws-cdk-lib/aws-ec2";
 import { IAccessPoint } from "aws-cdk-lib/aws-efs";
+import { Effect, PolicyStatement } from "aws-cdk-lib/aws-iam";
 import { Architecture, Code, FileSystem } from "aws-cdk-lib/aws-lambda";
+import { IBucket } from "aws-cdk-lib/aws-s3";
 import { Construct } from "constructs";
 import { RustLambdaFunction } from "./rust-lambda-function";
 
 export interface SearchSubtitleFunctionProps {
   readonly vpc: IVpc;
   readonly tantivyAccessPoint: IAccessPoint;
+  readonly bucket: IBucket;
 }
 
 export class SearchSubtitleFunction extends Construct {
@@ -37,7 +40,7 @@ export class SearchSubtitleFunction extends Construct {
   ) {
     super(scope, id);
 
-    const { vpc, tantivyAccessPoint } = props;
+    const { vpc, tantivyAccessPoint, bucket } = props;
 
     const mountPath = "/mnt/tantivy";
 
@@ -47,10 +50,21 @@ export class SearchSubtitleFunction extends Construct {
       architecture: Architecture.ARM_64,
       environment: {
         TANTIVY_MOUNT: mountPath,
+        BUCKET_NAME: bucket.bucketName,
       },
       timeout: Duration.seconds(5),
       memorySize: 512,
       filesystem: FileSystem.fromEfsAccessPoint(tantivyAccessPoint, mountPath),
     });
+    bucket.grantRead(this.rustFunction.func, "synonym/*");
+    // read replicas (INDEX_SOURCE=snapshot) load the index snapshots into memory.
+    bucket.grantRead(this.rustFunction.func, "snapshot/*");
+    this.rustFunction.func.addToRolePolicy(
+      new PolicyStatement({
+        effect: Effect.ALLOW,
+        actions: ["translate:TranslateText"],
+        resources: ["*"],
+      })
+    );
   }
 }
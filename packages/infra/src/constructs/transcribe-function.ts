@@ -47,6 +47,7 @@ export class TranscribeFunction extends Construct {
       architecture: Architecture.ARM_64,
       environment: {
         DYNAMODB_TABLE_NAME: dynamoDbTable.tableName,
+        BUCKET_NAME: eventSourceBucket.bucketName,
         IMAGE_FRAME_FUNCTION_NAME: imageFrameFunction.functionName,
       },
       timeout: Duration.seconds(15),
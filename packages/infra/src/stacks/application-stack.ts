@@ -23,9 +23,12 @@ import { AppApiGateway } from "../constructs/app-api-gateway";
 import { AppFunction } from "../constructs/app-function";
 import { ImageFrameFunction } from "../constructs/image-frame-function";
 import { IndexSubtitleFunction } from "../constructs/index-subtitle-function";
+import { IndexVideoFunction } from "../constructs/index-video-function";
 import { MediaDynamodb } from "../constructs/media-dynamodb";
 import { MediaStorage } from "../constructs/media-storage";
+import { MaintainIndexFunction } from "../constructs/maintain-index-function";
 import { MediaVpc } from "../constructs/media-vpc";
+import { ReindexFunction } from "../constructs/reindex-function";
 import { SearchSubtitleFunction } from "../constructs/search-subtitle-function";
 import { SubtitleFunction } from "../constructs/subtitle-function";
 import { SubtitleJobQueue } from "../constructs/subtitle-job-queue";
@@ -91,12 +94,31 @@ export class ApplicationStack extends Stack {
       subtitleResultTopic,
     });
 
+    new IndexVideoFunction(this, "IndexVideoFunction", {
+      vpc,
+      tantivyAccessPoint: tantivyIndexStorage.accessPoint,
+      dynamoDbTable: mediaDynamodb.table,
+    });
+
+    new ReindexFunction(this, "ReindexFunction", {
+      vpc,
+      tantivyAccessPoint: tantivyIndexStorage.accessPoint,
+      bucket: mediaStorage.bucket,
+    });
+
+    new MaintainIndexFunction(this, "MaintainIndexFunction", {
+      vpc,
+      tantivyAccessPoint: tantivyIndexStorage.accessPoint,
+      bucket: mediaStorage.bucket,
+    });
+
     const searchSubtitleFunction = new SearchSubtitleFunction(
       this,
       "SearchSubtitleFunction",
       {
         vpc,
         tantivyAccessPoint: tantivyIndexStorage.accessPoint,
+        bucket: mediaStorage.bucket,
       }
     );
 
@@ -105,7 +127,11 @@ export class ApplicationStack extends Stack {
       subtitleJobQueue: subtitleJobQueue.queue,
       searchSubtitleFunction: searchSubtitleFunction.rustFunction.func,
       dynamoDbTable: mediaDynamodb.table,
+      searchLogTable: mediaDynamodb.searchLogTable,
       distribution: mediaStorage.distribution,
+      bucket: mediaStorage.bucket,
+      subtitleResultTopic,
+      adminToken: this.node.tryGetContext("adminToken"),
     });
 
     new AppApiGateway(this, "AppApiGateway", {
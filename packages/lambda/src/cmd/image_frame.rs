@@ -17,49 +17,47 @@
  */
  
 use std::path::Path;
-use aws_sdk_s3::types::ByteStream;
 use lambda_runtime::{Error, service_fn, LambdaEvent};
-use tokio::fs::File;
-use tokio::io::{BufWriter};
 use tokio::process::Command;
 use lib::index::{ImageFrameEvent};
+use lib::storage::ObjectStore;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let func = service_fn(handler);
+    let objects = lib::storage::object_store_from_env().await;
+
+    // run locally, the function takes the frames spooled by the transcribe function.
+    if let Some(spool) = lib::queue::spool_from_env("IMAGE_FRAME_FUNCTION_NAME") {
+        lib::queue::run_spool(&spool, |body| frame_spooled(objects.as_ref(), body)).await?;
+        return Ok(());
+    }
+
+    let func = service_fn(|event| handler(objects.as_ref(), event));
     lambda_runtime::run(func).await?;
 
     Ok(())
 }
 
-async fn handler(event: LambdaEvent<ImageFrameEvent>) -> Result<(), Error> {
+async fn handler(objects: &dyn ObjectStore, event: LambdaEvent<ImageFrameEvent>) -> Result<(), Error> {
     println!("{:?}", event);
 
-    let bucket_name = dotenv::var("BUCKET_NAME")
-        .expect("BUCKET_NAME must be set.");
+    create_thumbnail(objects, &event.payload).await
+}
 
-    let shared_config = aws_config::from_env().load().await;
-    let s3 = aws_sdk_s3::Client::new(&shared_config);
+async fn frame_spooled(objects: &dyn ObjectStore, body: String) -> Result<(), Error> {
+    let event = serde_json::from_str::<ImageFrameEvent>(&body)?;
+    println!("{:?}", event);
 
-    let video_file_path = download_object(&s3, &bucket_name, &event.payload.video_key).await?;
+    create_thumbnail(objects, &event).await
+}
+
+async fn create_thumbnail(objects: &dyn ObjectStore, event: &ImageFrameEvent) -> Result<(), Error> {
+    let video_file_path = download_object(objects, &event.video_key).await?;
     println!("Video is downloaded at {}", video_file_path);
     let image_file_path = create_frame_image(&video_file_path).await?;
     println!("Image is created at {}", image_file_path);
-    upload_object(&s3, &bucket_name, &event.payload.thumbnail_key, &image_file_path).await?;
-    println!("file {} is uploader at s3 key {}", &image_file_path, &event.payload.thumbnail_key);
-
-    Ok(())
-}
-
-async fn upload_object(client: &aws_sdk_s3::Client, bucket: &str, key: &str, file_path: &str) -> Result<(), Error> {
-    let bs = ByteStream::from_path(file_path).await?;
-    client.put_object()
-        .bucket(bucket)
-        .key(key)
-        .body(bs)
-        .send()
-        .await
-        .unwrap();
+    objects.upload(&event.thumbnail_key, Path::new(&image_file_path)).await?;
+    println!("file {} is uploader at s3 key {}", &image_file_path, &event.thumbnail_key);
 
     Ok(())
 }
@@ -75,21 +73,11 @@ async fn create_frame_image(file_path: &str) -> Result<String, Error> {
     Ok(output_file_path.to_string())
 }
 
-async fn download_object(client: &aws_sdk_s3::Client, bucket: &str, key: &str) -> Result<String, Error> {
-    let output = client.get_object()
-        .bucket(bucket)
-        .key(key)
-        .send()
-        .await?;
-
+async fn download_object(objects: &dyn ObjectStore, key: &str) -> Result<String, Error> {
     let file_ext = Path::new(key).extension().and_then(|s| s.to_str()).expect("file extension must be exist");
     let file_path = format!("/tmp/video.{}", file_ext);
 
-    let mut r = output.body.into_async_read();
-    let file = File::create(&file_path).await?;
-    let mut w = BufWriter::new(file);
-
-    tokio::io::copy(&mut r, &mut w).await?;
+    objects.download(key, Path::new(&file_path)).await?;
 
     Ok(file_path)
 }
\ No newline at end of file
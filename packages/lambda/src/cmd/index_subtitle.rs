@@ -16,55 +16,232 @@
  * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
  
-use aws_lambda_events::event::sns::SnsEvent;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use aws_lambda_events::event::sqs::SqsEvent;
 use lambda_runtime::{Error, service_fn, LambdaEvent};
-use tantivy::{doc, Term};
-use lib::index::IndexTopicMessage;
+use serde::Serialize;
+use tantivy::{IndexWriter, Term};
+use lib::index::{IndexAction, IndexTopicMessage};
+use lib::tantivy::{SchemaMismatch, VideoCues};
+use lib::vector::Embedder;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    // run locally, the function takes the messages spooled for the index topic.
+    if let Some(spool) = lib::queue::spool_from_env("INDEX_TOPIC_ARN") {
+        let mount = dotenv::var("TANTIVY_MOUNT")
+            .expect("TANTIVY_MOUNT must be set.");
+        let embedder = lib::vector::embedder_from_env();
+        lib::queue::run_spool(&spool, |body| index_spooled(&mount, embedder.as_deref(), body)).await?;
+        return Ok(());
+    }
+
     let func = service_fn(handler);
     lambda_runtime::run(func).await?;
 
     Ok(())
 }
 
-async fn handler(event: LambdaEvent<SnsEvent>) -> Result<(), Error> {
+// messages reported here are redelivered by the queue, the others are deleted.
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct BatchResponse {
+    batch_item_failures: Vec<BatchItemFailure>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchItemFailure {
+    item_identifier: String,
+}
+
+async fn handler(event: LambdaEvent<SqsEvent>) -> Result<BatchResponse, Error> {
 
     println!("{:?}", serde_json::to_string(&event.payload).unwrap());
 
     let mount = dotenv::var("TANTIVY_MOUNT")
         .expect("TANTIVY_MOUNT must be set.");
+    let embedder = lib::vector::embedder_from_env();
 
-    for record in event.payload.records {
-        let message = record.sns.message;
-        let msg = serde_json::from_str::<IndexTopicMessage>(&message)
-            .unwrap_or_else(| _| panic!("invalid message: {}", message));
+    let records = event.payload.records.into_iter()
+        .map(|record| (record.message_id.unwrap_or_default(), record.body.unwrap_or_default()))
+        .collect::<Vec<_>>();
+    let failures = index_records(&mount, embedder.as_deref(), records).await?;
 
-        let schema = lib::tantivy::tantivy_schema(&msg.lang);
-        let index = lib::tantivy::tantivy_index(&mount, &msg.lang).unwrap();
-        let mut index_writer = index.writer(50_000_000).unwrap();
+    Ok(BatchResponse {
+        batch_item_failures: failures.into_iter()
+            .map(|message_id| BatchItemFailure { item_identifier: message_id })
+            .collect(),
+    })
+}
 
-        let video_id_field = schema.get_field("video_id").unwrap();
-        let time_field = schema.get_field("time").unwrap();
-        let body_field = schema.get_field("body").unwrap();
+// a spooled message is a batch of its own.
+async fn index_spooled(mount: &str, embedder: Option<&dyn Embedder>, body: String) -> Result<(), Error> {
+    let failures = index_records(mount, embedder, vec![("spool".to_string(), body)]).await?;
+    if !failures.is_empty() {
+        return Err("the message was not indexed".into());
+    }
 
-        // delete exist video_id docs.
-        index_writer.delete_term(Term::from_field_text(video_id_field, &msg.video_id));
-        index_writer.commit().unwrap();
-
-        for line in msg.body.lines() {
-            if let Some((time, body)) = line.split_once(' ') {
-                index_writer.add_document(doc! {
-                    video_id_field => msg.video_id.as_str(),
-                    time_field => time,
-                    body_field => body
-                }).unwrap();
+    Ok(())
+}
+
+// indexes the messages given with their ids, and returns the ids of the messages that failed.
+async fn index_records(mount: &str,
+                       embedder: Option<&dyn Embedder>,
+                       records: Vec<(String, String)>) -> Result<BTreeSet<String>, Error> {
+    // a message of several languages is redelivered when any of them fails.
+    let mut failures: BTreeSet<String> = BTreeSet::new();
+    // messages are indexed per language, with one writer session for all of them.
+    let mut batches: BTreeMap<String, Vec<(String, IndexTopicMessage)>> = BTreeMap::new();
+    for (message_id, body) in records {
+        match serde_json::from_str::<IndexTopicMessage>(&body) {
+            Ok(msg) if msg.action == IndexAction::Delete => {
+                // a language without an index has nothing to delete, and no index is created for it.
+                let langs = lib::tantivy::indexed_langs(mount)?.into_iter()
+                    .filter(|lang| msg.lang.is_empty() || *lang == msg.lang);
+                for lang in langs {
+                    batches.entry(lang.clone()).or_default().push((message_id.clone(), IndexTopicMessage {
+                        video_id: msg.video_id.clone(),
+                        lang,
+                        action: IndexAction::Delete,
+                        ..Default::default()
+                    }));
+                }
+            }
+            Ok(msg) => batches.entry(msg.lang.clone()).or_default().push((message_id, msg)),
+            Err(e) => {
+                println!("invalid message {}: {}", message_id, e);
+                failures.insert(message_id);
             }
         }
+    }
+
+    for (lang, messages) in batches {
+        let result = index_messages(mount, &lang, &messages, embedder).await;
+        if let Err(e) = result {
+            println!("failed to index {} messages of {}: {}", messages.len(), lang, e);
+            failures.extend(messages.into_iter().map(|(message_id, _)| message_id));
+        }
+    }
+
+    Ok(failures)
+}
+
+async fn index_messages(mount: &str,
+                        lang: &str,
+                        messages: &[(String, IndexTopicMessage)],
+                        embedder: Option<&dyn Embedder>) -> Result<(), Error> {
+    let schema = lib::tantivy::tantivy_schema(lang);
+    let mut index_writer = open_writer(mount, lang).await?;
+    // a reindex in progress indexes these videos again before it switches over.
+    lib::migration::record_replay(mount, lang, messages.iter().map(|(_, msg)| msg.video_id.as_str()))?;
+
+    let video_id_field = schema.get_field("video_id").unwrap();
+
+    for (_, msg) in messages {
+        // the delete only applies to documents added before it, so both go into one commit.
+        index_writer.delete_term(Term::from_field_text(video_id_field, &msg.video_id));
+        if msg.action == IndexAction::Delete {
+            continue;
+        }
+        let cues = lib::tantivy::body_cues(&msg.body);
+        lib::tantivy::add_cue_documents(&index_writer, &schema, &VideoCues {
+            video_id: &msg.video_id,
+            lang,
+            created_at: msg.created_at.unwrap_or_default(),
+            cues: &cues,
+            confidences: &msg.confidences,
+            speakers: &msg.speakers,
+        })?;
+    }
+    index_writer.commit()?;
+
+    // suggestions and vectors are written while the writer lock is held, so no other batch of
+    // the language writes them at the same time.
+    for (_, msg) in messages {
+        // deleting a video twice finds nothing to remove, so redelivered messages are harmless.
+        if msg.action == IndexAction::Delete {
+            lib::suggest::remove_video_suggestions(mount, lang, &msg.video_id)?;
+            lib::vector::remove_video_vectors(mount, lang, &msg.video_id)?;
+            continue;
+        }
 
-        index_writer.commit().unwrap();
+        lib::suggest::index_video_suggestions(mount, lang, &msg.video_id, &msg.body)?;
+
+        if let Some(embedder) = embedder {
+            let cues = lib::tantivy::body_cues(&msg.body);
+            lib::vector::index_video_vectors(mount, lang, &msg.video_id, &cues, embedder)?;
+        }
     }
+    lib::suggest::rebuild_lang_suggestions(mount, lang)?;
+
+    // releases the writer lock for the next batch.
+    drop(index_writer);
 
     Ok(())
+}
+
+// an index of an older schema fails the batch, the messages are redelivered until the reindex
+// function has rebuilt it. the reindex switches the index while it holds the writer lock, so a
+// writer that waited for the lock opens the index again.
+async fn open_writer(mount: &str, lang: &str) -> Result<IndexWriter, Error> {
+    let live_path = Path::new(mount).join(lang);
+    loop {
+        fs::create_dir_all(&live_path)?;
+        let target = fs::canonicalize(&live_path)?;
+        let index = match lib::tantivy::tantivy_index(mount, lang) {
+            Ok(index) => index,
+            Err(e) if e.is::<SchemaMismatch>() => {
+                println!("{}, waiting for the reindex function", e);
+                return Err(e.into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let index_writer = lib::tantivy::index_writer(&index).await?;
+        if fs::canonicalize(&live_path)? == target {
+            return Ok(index_writer);
+        }
+        println!("index {} was switched while waiting for the writer, open it again", lang);
+    }
+}
+
+// the guard of the lib tests, which are not built for the tests of a function.
+#[cfg(test)]
+#[path = "../lib/test_dir.rs"]
+#[allow(dead_code)]
+mod test_dir;
+
+#[cfg(test)]
+mod tests {
+    use lib::index::IndexTopicMessage;
+    use lib::queue::{Queue, SpoolQueue};
+    use crate::index_spooled;
+    use crate::test_dir::TestDir;
+
+    // the function run locally: it takes the subtitles spooled by the subtitle function. a message that
+    // can't be indexed stays in the spool.
+    #[tokio::test]
+    async fn spooled_index_test() {
+        let dir = TestDir::new("index-subtitle-test");
+        let root = dir.path();
+        let mount = root.join("tantivy").to_string_lossy().to_string();
+        let spool = SpoolQueue::new(root.join("queues"), "index").unwrap();
+        let message = IndexTopicMessage {
+            video_id: "a".to_string(),
+            lang: "en".to_string(),
+            body: "00:00:01.000 hello world\n".to_string(),
+            ..Default::default()
+        };
+        spool.send(&serde_json::to_string(&message).unwrap()).await.unwrap();
+        spool.send("{").await.unwrap();
+
+        let count = lib::queue::drain_spool(&spool, |body| index_spooled(&mount, None, body)).await.unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(spool.receive().unwrap().as_deref(), Some("{"));
+
+        let index = lib::tantivy::tantivy_index(&mount, "en").unwrap();
+        assert_eq!(index.reader().unwrap().searcher().num_docs(), 1);
+    }
 }
\ No newline at end of file
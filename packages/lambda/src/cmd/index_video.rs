@@ -0,0 +1,78 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use aws_lambda_events::event::dynamodb::Event;
+use lambda_runtime::{Error, service_fn, LambdaEvent};
+use serde::Deserialize;
+use tantivy::{doc, Term};
+use lib::index::VideoDocument;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let func = service_fn(handler);
+    lambda_runtime::run(func).await?;
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct VideoKey {
+    id: String,
+}
+
+async fn handler(event: LambdaEvent<Event>) -> Result<(), Error> {
+
+    println!("{:?}", serde_json::to_string(&event.payload).unwrap());
+
+    let mount = dotenv::var("TANTIVY_MOUNT")
+        .expect("TANTIVY_MOUNT must be set.");
+
+    let schema = lib::tantivy::tantivy_video_schema();
+    let index = lib::tantivy::tantivy_video_index(&mount)?;
+    let mut index_writer = lib::tantivy::index_writer(&index).await?;
+
+    let video_id_field = schema.get_field("video_id").unwrap();
+    let title_field = schema.get_field("title").unwrap();
+    let lang_field = schema.get_field("lang").unwrap();
+    let keywords_field = schema.get_field("keywords").unwrap();
+
+    for record in event.payload.records {
+        let key: VideoKey = serde_dynamo::from_item(record.change.keys)?;
+
+        // every change of the item replaces the video document.
+        index_writer.delete_term(Term::from_field_text(video_id_field, &key.id));
+        if record.event_name == "REMOVE" {
+            continue;
+        }
+
+        let video: VideoDocument = serde_dynamo::from_item(record.change.new_image)?;
+        let mut document = doc! {
+            video_id_field => video.id.as_str(),
+            title_field => video.title.as_str(),
+            lang_field => video.lang.as_str()
+        };
+        for keyword in video.keywords.iter() {
+            document.add_text(keywords_field, keyword);
+        }
+        index_writer.add_document(document)?;
+    }
+
+    index_writer.commit()?;
+
+    Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,68 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use lambda_runtime::{Error, service_fn, LambdaEvent};
+use serde_json::Value;
+use lib::index::{MaintenanceAction, MaintenanceEvent};
+use lib::storage::ObjectStore;
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let store = lib::storage::object_store_from_env().await;
+
+    let func = service_fn(|event| handler(store.as_ref(), event));
+    lambda_runtime::run(func).await?;
+
+    Ok(())
+}
+
+async fn handler(store: &dyn ObjectStore, event: LambdaEvent<MaintenanceEvent>) -> Result<Vec<Value>, Error> {
+
+    println!("{:?}", serde_json::to_string(&event.payload).unwrap());
+
+    let mount = dotenv::var("TANTIVY_MOUNT")
+        .expect("TANTIVY_MOUNT must be set.");
+
+    let names = if event.payload.indexes.is_empty() && event.payload.action == MaintenanceAction::Restore {
+        return Err("indexes must be set for restore.".into());
+    } else if event.payload.indexes.is_empty() {
+        lib::maintenance::index_names(&mount)?
+    } else {
+        event.payload.indexes.clone()
+    };
+
+    let mut output = vec![];
+    for name in names {
+        let value = match event.payload.action {
+            MaintenanceAction::Optimize => {
+                let result = lib::maintenance::optimize_index(&mount, &name).await?;
+                println!("{:?}", result);
+                serde_json::to_value(result)?
+            }
+            MaintenanceAction::Stats => serde_json::to_value(lib::maintenance::index_stats(&mount, &name)?)?,
+            MaintenanceAction::Snapshot => serde_json::to_value(lib::snapshot::create_snapshot(store, &mount, &name).await?)?,
+            MaintenanceAction::Restore => {
+                let version = event.payload.version.as_deref();
+                serde_json::to_value(lib::snapshot::restore_snapshot(store, &mount, &name, version).await?)?
+            }
+        };
+        output.push(value);
+    }
+
+    Ok(output)
+}
\ No newline at end of file
@@ -0,0 +1,71 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use lambda_runtime::{Error, service_fn, LambdaEvent};
+use lib::index::ReindexEvent;
+use lib::migration::ReindexProgress;
+use lib::storage::ObjectStore;
+
+// time left for the last commit and the state when a run stops before the lambda timeout.
+const STOP_MARGIN: Duration = Duration::from_secs(60);
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let objects = lib::storage::object_store_from_env().await;
+
+    let func = service_fn(|event| handler(objects.as_ref(), event));
+    lambda_runtime::run(func).await?;
+
+    Ok(())
+}
+
+// rebuilds the language indexes from the stored subtitles. a run that is stopped by the deadline
+// reports `complete: false` and is continued by invoking the function again.
+async fn handler(objects: &dyn ObjectStore, event: LambdaEvent<ReindexEvent>) -> Result<Vec<ReindexProgress>, Error> {
+
+    println!("{:?}", serde_json::to_string(&event.payload).unwrap());
+
+    let mount = dotenv::var("TANTIVY_MOUNT")
+        .expect("TANTIVY_MOUNT must be set.");
+
+    let langs = if event.payload.langs.is_empty() {
+        lib::migration::list_subtitle_langs(objects).await?
+    } else {
+        event.payload.langs.clone()
+    };
+
+    let deadline = UNIX_EPOCH + Duration::from_millis(event.context.deadline);
+    let should_stop = || SystemTime::now() + STOP_MARGIN > deadline;
+
+    let mut output = vec![];
+    for lang in langs {
+        if should_stop() {
+            break;
+        }
+        let progress = lib::migration::reindex(
+            objects,
+            &mount,
+            &lang,
+            !event.payload.restart,
+            &should_stop).await?;
+        output.push(progress);
+    }
+
+    Ok(output)
+}
\ No newline at end of file
@@ -16,55 +16,88 @@
  * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
  
+use std::sync::Arc;
 use lambda_runtime::{Error, service_fn, LambdaEvent};
-use tantivy::doc;
-use serde::{Serialize, Deserialize};
 use serde_json::Value;
-use tantivy::collector::{TopDocs};
-use tantivy::query::QueryParser;
+use tokio::sync::Mutex;
+use lib::cache::IndexCache;
+use lib::search::TantivySearchEvent;
+use lib::storage::S3ObjectStore;
+use lib::synonym::{SynonymCache, Synonyms};
+use lib::translate::AwsTranslator;
+use lib::vector::Embedder;
+
+// state kept across invocations.
+struct SearchFunction {
+    s3: aws_sdk_s3::Client,
+    bucket_name: String,
+    // opened indexes, readers and tokenizers.
+    indexes: Arc<IndexCache>,
+    snapshot_store: Arc<S3ObjectStore>,
+    // synonyms are reloaded when the object in the bucket changes.
+    synonym_cache: Mutex<SynonymCache>,
+    translator: AwsTranslator,
+    embedder: Option<Box<dyn Embedder>>,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let func = service_fn(handler);
+    let shared_config = aws_config::from_env().load().await;
+    let s3 = aws_sdk_s3::Client::new(&shared_config);
+    let mount = dotenv::var("TANTIVY_MOUNT")
+        .expect("TANTIVY_MOUNT must be set.");
+    let bucket_name = dotenv::var("BUCKET_NAME")
+        .expect("BUCKET_NAME must be set.");
+
+    let function = SearchFunction {
+        s3: s3.clone(),
+        indexes: Arc::new(IndexCache::from_env(&mount)),
+        snapshot_store: Arc::new(S3ObjectStore::new(s3, &bucket_name)),
+        bucket_name,
+        synonym_cache: Mutex::new(SynonymCache::default()),
+        translator: AwsTranslator::new(aws_sdk_translate::Client::new(&shared_config)),
+        embedder: lib::vector::embedder_from_env(),
+    };
+
+    // the first request loads the snapshots, newer versions are loaded in the background.
+    if function.indexes.is_replica() {
+        lib::cache::spawn_snapshot_refresh(function.indexes.clone(), function.snapshot_store.clone());
+    }
+
+    let func = service_fn(|event| handler(&function, event));
     lambda_runtime::run(func).await?;
 
     Ok(())
 }
 
-#[derive(Serialize, Deserialize)]
-struct TantivySearchEvent {
-    lang: String,
-    query: String
-}
-
-async fn handler(event: LambdaEvent<TantivySearchEvent>) -> Result<Vec<Value>, Error> {
+async fn handler(function: &SearchFunction, event: LambdaEvent<TantivySearchEvent>) -> Result<Vec<Value>, Error> {
 
     println!("{:?}", serde_json::to_string(&event.payload).unwrap());
 
-    let mount = dotenv::var("TANTIVY_MOUNT")
-        .expect("TANTIVY_MOUNT must be set.");
-
-    let schema = lib::tantivy::tantivy_schema(&event.payload.lang);
-    let index = lib::tantivy::tantivy_index(&mount, &event.payload.lang)?;
-    let video_id_field = schema.get_field("video_id").unwrap();
-    let body_field = schema.get_field("body").unwrap();
-
-    let index_reader = index.reader().unwrap();
-    let searcher = index_reader.searcher();
-    let query_parser = QueryParser::for_index(&index, vec![video_id_field, body_field]);
-
-    let query = query_parser.parse_query(&event.payload.query)?;
+    let indexes = function.indexes.as_ref();
+    if indexes.is_replica() && !indexes.has_snapshots() {
+        indexes.refresh_snapshots(function.snapshot_store.as_ref()).await;
+    }
 
-    let collector = TopDocs::with_limit(10);
-    let top_docs = searcher.search(&query, &collector)?;
+    // suggestions, related videos and stats don't need the synonyms.
+    let synonyms = if event.payload.action.uses_synonyms() {
+        function.synonym_cache.lock().await
+            .get(&function.s3, &function.bucket_name, &event.payload.lang)
+            .await
+            .unwrap_or_else(|e| {
+                println!("failed to load synonyms: {:?}", e);
+                Arc::new(Synonyms::default())
+            })
+    } else {
+        Arc::new(Synonyms::default())
+    };
 
-    let output = top_docs.into_iter()
-        .map(|(_score, doc_address)| {
-            let doc = searcher.doc(doc_address).unwrap();
-            let json_str = schema.to_json(&doc);
-            serde_json::from_str::<Value>(&json_str).unwrap()
-        })
-        .collect::<Vec<_>>();
+    let output = lib::search::execute(
+        indexes,
+        &event.payload,
+        &synonyms,
+        &function.translator,
+        function.embedder.as_deref()).await?;
 
     Ok(output)
 }
\ No newline at end of file
@@ -17,133 +17,148 @@
  */
  
 use aws_lambda_events::event::sqs::SqsEvent;
-use aws_sdk_dynamodb::model::AttributeValue;
-use aws_sdk_s3::types::ByteStream;
-use itertools::Itertools;
 use lambda_runtime::{Error, service_fn, LambdaEvent};
-use serde_dynamo::{from_attribute_value, to_attribute_value};
-use lib::index::IndexTopicMessage;
+use lib::catalogue::VideoCatalogue;
+use lib::index::{IndexAction, IndexTopicMessage};
+use lib::queue::Topic;
+use lib::storage::ObjectStore;
 use lib::subtitle::{Subtitle, SubtitleQueueMessage};
 
+struct SubtitleFunction {
+    translate: aws_sdk_translate::Client,
+    objects: Box<dyn ObjectStore>,
+    catalogue: Box<dyn VideoCatalogue>,
+    // subscribed by the index function.
+    index_topic: Box<dyn Topic>,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let func = service_fn(handler);
+    let shared_config = aws_config::from_env().load().await;
+
+    let function = SubtitleFunction {
+        translate: aws_sdk_translate::Client::new(&shared_config),
+        objects: lib::storage::object_store_from_env().await,
+        catalogue: lib::catalogue::catalogue_from_env().await,
+        index_topic: lib::queue::topic_from_env("TOPIC_ARN").await,
+    };
+
+    // run locally, the function takes the messages the app spooled.
+    if let Some(spool) = lib::queue::spool_from_env("SUBTITLE_QUEUE_URL") {
+        lib::queue::run_spool(&spool, |body| handle_message(&function, body)).await?;
+        return Ok(());
+    }
+
+    let func = service_fn(|event| handler(&function, event));
     lambda_runtime::run(func).await?;
 
     Ok(())
 }
 
-async fn handler(event: LambdaEvent<SqsEvent>) -> Result<(), Error> {
+async fn handler(function: &SubtitleFunction, event: LambdaEvent<SqsEvent>) -> Result<(), Error> {
 
     println!("{:?}", serde_json::to_string(&event.payload).unwrap());
 
-    let table_name = dotenv::var("DYNAMODB_TABLE_NAME")
-        .expect("DYNAMODB_TABLE_NAME must be set.");
-    let bucket_name = dotenv::var("BUCKET_NAME")
-        .expect("BUCKET_NAME must be set.");
-    let topic_arn = dotenv::var("TOPIC_ARN")
-        .expect("TOPIC_ARN must be set.");
-    let shared_config = aws_config::from_env()
-        .load().await;
-
-    let dynamodb = aws_sdk_dynamodb::Client::new(&shared_config);
-    let s3 = aws_sdk_s3::Client::new(&shared_config);
-    let translate = aws_sdk_translate::Client::new(&shared_config);
-    let sns = aws_sdk_sns::Client::new(&shared_config);
-
     for record in event.payload.records {
         let body = record.body.expect("message body must be exist");
-        let msg = serde_json::from_str::<SubtitleQueueMessage>(&body)
-            .unwrap_or_else(|_| panic!("invalid message: {}", body));
-
-        let transcription_key = format!("transcription/{}", msg.video_id);
-        let json = load_text_object(&s3, &bucket_name, &transcription_key).await.unwrap();
-
-        let mut subtitle = Subtitle::from_transcribe_output(&json).unwrap();
-
-        let lang = if let Some(target_language) = msg.translate_language {
-            subtitle.translate(&translate, &msg.content_language, &target_language).await.unwrap();
-            target_language
-        } else {
-            msg.content_language
-        };
-
-        let vtt = subtitle.vtt();
-        put_object(&s3, &bucket_name, &format!("subtitle/{}/{}.vtt", msg.video_id, lang), vtt.as_bytes()).await.unwrap();
-        update_subtitle(&dynamodb, &table_name, &msg.video_id, &lang).await.unwrap();
-        publish_message_to_topic(&sns, &topic_arn, &IndexTopicMessage{
-            video_id: msg.video_id.clone(),
-            lang,
-            body: subtitle.index_body()
-        }).await.unwrap();
+        handle_message(function, body).await?;
     }
 
     Ok(())
 }
 
-async fn load_text_object(client: &aws_sdk_s3::Client, bucket: &str, key: &str) -> Result<String, Error> {
-    let output = client.get_object()
-        .bucket(bucket)
-        .key(key)
-        .send()
-        .await?;
-
-    let bs = output.body.collect().await?.into_bytes();
-    let text = std::str::from_utf8(&bs)?;
-
-    Ok(text.to_string())
-}
-
-async fn put_object(client: &aws_sdk_s3::Client, bucket: &str, key: &str, content: &[u8]) -> Result<aws_sdk_s3::output::PutObjectOutput, Error> {
+async fn handle_message(function: &SubtitleFunction, body: String) -> Result<(), Error> {
+    let msg = serde_json::from_str::<SubtitleQueueMessage>(&body)
+        .map_err(|e| format!("invalid message {}: {}", body, e))?;
+
+    let transcription_key = format!("transcription/{}", msg.video_id);
+    let json = lib::storage::get_text(function.objects.as_ref(), &transcription_key).await?;
+
+    let mut subtitle = Subtitle::from_transcribe_output(&json)?;
+
+    let lang = if let Some(target_language) = msg.translate_language {
+        subtitle.translate(&function.translate, &msg.content_language, &target_language).await?;
+        target_language
+    } else {
+        msg.content_language
+    };
+
+    let vtt = subtitle.vtt();
+    function.objects.put(&lib::subtitle::subtitle_key(&msg.video_id, &lang), vtt.into_bytes()).await?;
+    // the upload time of the video, for ranking.
+    let created_at = lib::catalogue::add_subtitle(function.catalogue.as_ref(), &msg.video_id, &lang).await?
+        .and_then(|video| lib::index::parse_created_at(&video.created_at));
+    let message = IndexTopicMessage{
+        video_id: msg.video_id.clone(),
+        lang,
+        body: subtitle.index_body(),
+        action: IndexAction::Index,
+        confidences: subtitle.index_confidences(),
+        speakers: subtitle.index_speakers(),
+        created_at,
+    };
+    function.index_topic.publish(&serde_json::to_string(&message).unwrap()).await?;
 
-    let bs = ByteStream::from(content.to_vec());
-    let output = client.put_object()
-        .bucket(bucket)
-        .key(key)
-        .body(bs)
-        .send()
-        .await?;
-
-    Ok(output)
+    Ok(())
 }
 
-async fn update_subtitle(client: &aws_sdk_dynamodb::Client, table_name: &str, id: &str, lang: &str) -> Result<(), Error> {
-    let item_output = client.get_item()
-        .table_name(table_name)
-        .key("id", AttributeValue::S(id.to_owned()))
-        .send()
-        .await?;
-
-    if let Some(item) = item_output.item {
+// the guard of the lib tests, which are not built for the tests of a function.
+#[cfg(test)]
+#[path = "../lib/test_dir.rs"]
+#[allow(dead_code)]
+mod test_dir;
+
+#[cfg(test)]
+mod tests {
+    use lib::catalogue::{FileVideoCatalogue, VideoCatalogue, VideoItem};
+    use lib::index::IndexTopicMessage;
+    use lib::queue::{Queue, SpoolQueue};
+    use lib::storage::{LocalObjectStore, ObjectStore};
+    use lib::subtitle::SubtitleQueueMessage;
+    use crate::{handle_message, SubtitleFunction};
+    use crate::test_dir::TestDir;
+
+    // the function run locally: it takes the request spooled by the app and spools the subtitle for
+    // the index function.
+    #[tokio::test]
+    async fn spooled_subtitle_test() {
+        let dir = TestDir::new("subtitle-test");
+        let root = dir.path();
+        let objects = LocalObjectStore::new(root.join("objects"));
+        let json = r#"{"jobName": "job", "accountId": "1", "status": "COMPLETED", "results": {"items": [
+            {"type": "pronunciation", "start_time": "1.0", "end_time": "1.5", "alternatives": [{"confidence": "1.0", "content": "hello"}]},
+            {"type": "pronunciation", "start_time": "1.5", "end_time": "2.0", "alternatives": [{"confidence": "1.0", "content": "world"}]}
+        ]}}"#;
+        objects.put("transcription/a", json.as_bytes().to_vec()).await.unwrap();
+        let catalogue = FileVideoCatalogue::new(root.join("catalogue.json"));
+        catalogue.put_video(&VideoItem {
+            id: "a".to_string(),
+            title: "title".to_string(),
+            lang: "en-US".to_string(),
+            ..Default::default()
+        }).await.unwrap();
 
-        let subtitles = if let Some(v) = item.get("subtitles") {
-            let mut ss: Vec<String> = from_attribute_value(v.to_owned()).unwrap();
-            ss.push(lang.to_string());
+        let queues = root.join("queues");
+        let message = SubtitleQueueMessage::new("a", "en", None);
+        SpoolQueue::new(&queues, "subtitle").unwrap()
+            .send(&serde_json::to_string(&message).unwrap()).await.unwrap();
 
-            ss.into_iter().unique().collect()
-        } else {
-            vec![lang.to_string()]
+        let function = SubtitleFunction {
+            translate: aws_sdk_translate::Client::from_conf(aws_sdk_translate::Config::builder().build()),
+            objects: Box::new(objects),
+            catalogue: Box::new(catalogue),
+            index_topic: Box::new(SpoolQueue::new(&queues, "index").unwrap()),
         };
-
-        client.update_item()
-            .table_name(table_name)
-            .key("id", AttributeValue::S(id.to_owned()))
-            .update_expression("SET subtitles = :subtitles")
-            .expression_attribute_values(":subtitles", to_attribute_value(subtitles).unwrap())
-            .send()
-            .await?;
+        let spool = SpoolQueue::new(&queues, "subtitle").unwrap();
+        let count = lib::queue::drain_spool(&spool, |body| handle_message(&function, body)).await.unwrap();
+        assert_eq!(count, 1);
+
+        let vtt = lib::storage::get_text(function.objects.as_ref(), "subtitle/a/en.vtt").await.unwrap();
+        assert!(vtt.contains("hello world"));
+        assert_eq!(function.catalogue.find_video("a").await.unwrap().unwrap().subtitles, vec!["en".to_string()]);
+        let body = SpoolQueue::new(&queues, "index").unwrap().receive().unwrap().unwrap();
+        let message = serde_json::from_str::<IndexTopicMessage>(&body).unwrap();
+        assert_eq!((message.video_id.as_str(), message.lang.as_str()), ("a", "en"));
+        assert!(message.body.contains("hello world"));
     }
-
-    Ok(())
-}
-
-async fn publish_message_to_topic(client: &aws_sdk_sns::Client, topic_arn: &str, message: &IndexTopicMessage) -> Result<(), Error> {
-
-    client.publish()
-        .topic_arn(topic_arn)
-        .message(serde_json::to_string(message).unwrap())
-        .send()
-        .await?;
-
-    Ok(())
 }
\ No newline at end of file
@@ -18,36 +18,46 @@
  
 use std::path::Path;
 use aws_lambda_events::s3::S3Event;
-use aws_sdk_dynamodb::model::AttributeValue;
-use aws_sdk_lambda::types::ByteStream;
-use aws_sdk_transcribe::model::{LanguageCode, Media};
+use aws_sdk_transcribe::model::{LanguageCode, Media, Settings};
 use chrono::Utc;
 use lambda_runtime::{Error, LambdaEvent, service_fn};
 use uuid::Uuid;
+use lib::catalogue::{VideoCatalogue, VideoItem};
 use lib::index::ImageFrameEvent;
+use lib::queue::Queue;
+use lib::storage::ObjectStore;
+
+// speakers told apart by the transcription, 2 to 30 are allowed.
+const MAX_SPEAKER_LABELS: i32 = 10;
+
+struct TranscribeFunction {
+    transcribe: aws_sdk_transcribe::Client,
+    objects: Box<dyn ObjectStore>,
+    catalogue: Box<dyn VideoCatalogue>,
+    // the image frame function, which takes the thumbnail of the video.
+    image_frame_queue: Box<dyn Queue>,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    let func = service_fn(handler);
+    let shared_config = aws_config::from_env().load().await;
+
+    let function = TranscribeFunction {
+        transcribe: aws_sdk_transcribe::Client::new(&shared_config),
+        objects: lib::storage::object_store_from_env().await,
+        catalogue: lib::catalogue::catalogue_from_env().await,
+        image_frame_queue: lib::queue::function_queue_from_env("IMAGE_FRAME_FUNCTION_NAME").await,
+    };
+
+    let func = service_fn(|event| handler(&function, event));
     lambda_runtime::run(func).await?;
 
     Ok(())
 }
 
-async fn handler(event: LambdaEvent<S3Event>) -> Result<(), Error> {
+async fn handler(function: &TranscribeFunction, event: LambdaEvent<S3Event>) -> Result<(), Error> {
     println!("{:?}", serde_json::to_string(&event.payload).unwrap());
 
-    let table_name = dotenv::var("DYNAMODB_TABLE_NAME")
-        .expect("DYNAMODB_TABLE_NAME must be set.");
-    let image_frame_function_name = dotenv::var("IMAGE_FRAME_FUNCTION_NAME")
-        .expect("IMAGE_FRAME_FUNCTION_NAME must be set");
-
-    let shared_config = aws_config::from_env().load().await;
-
-    let dynamodb = aws_sdk_dynamodb::Client::new(&shared_config);
-    let transcribe = aws_sdk_transcribe::Client::new(&shared_config);
-    let lambda = aws_sdk_lambda::Client::new(&shared_config);
-
     for record in event.payload.records {
         let bucket = record.s3.bucket.name.expect("object bucket must be set");
         let key = record.s3.object.key.expect("object key must be set");
@@ -72,43 +82,49 @@ async fn handler(event: LambdaEvent<S3Event>) -> Result<(), Error> {
                 .media_file_uri(&media_uri)
                 .build();
 
-            transcribe.start_transcription_job()
+            function.transcribe.start_transcription_job()
                 .language_code(LanguageCode::from(lang))
                 .transcription_job_name(&id)
                 .media(media)
+                .settings(Settings::builder()
+                    .show_speaker_labels(true)
+                    .max_speaker_labels(MAX_SPEAKER_LABELS)
+                    .build())
                 .output_bucket_name(&bucket)
                 .output_key(format!("transcription/{}", &id))
                 .send()
-                .await
-                .unwrap();
+                .await?;
 
-            let thumbnail_key = format!("thumbnail/{}.jpg", id);
+            // optional keywords are given as object metadata, e.g. `x-amz-meta-keywords: k8s,eks`
+            let keywords = function.objects.head(&url_decode(&key))
+                .await
+                .ok()
+                .and_then(|head| head.metadata.get("keywords").cloned())
+                .map(|k| k.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>())
+                .unwrap_or_default();
+
+            let thumbnail_key = lib::index::thumbnail_key(&id);
             let image_frame_payload = ImageFrameEvent{
                 video_id: id.to_string(),
                 video_key: url_decode(&key),
                 thumbnail_key: thumbnail_key.to_string()
             };
-            let payload_bytes = serde_json::to_vec_pretty(&image_frame_payload).unwrap();
+            function.image_frame_queue.send(&serde_json::to_string_pretty(&image_frame_payload).unwrap())
+                .await?;
 
-            lambda.invoke_async()
-                .function_name(&image_frame_function_name)
-                .invoke_args(ByteStream::from(payload_bytes))
-                .send()
-                .await
-                .unwrap();
-
-            dynamodb.put_item()
-                .table_name(&table_name)
-                .item("id", AttributeValue::S(id.clone()))
-                .item("created_at", AttributeValue::S(Utc::now().to_string()))
-                .item("video_key", AttributeValue::S(url_decode(&key)))
-                .item("thumbnail_key", AttributeValue::S(url_decode(&thumbnail_key)))
-                .item("title", AttributeValue::S(url_decode(title)))
-                .item("lang", AttributeValue::S(lang.to_string()))
-                .item("subtitles", AttributeValue::L(vec![]))
-                .send()
-                .await
-                .unwrap();
+            function.catalogue.put_video(&VideoItem {
+                id: id.clone(),
+                created_at: Utc::now().to_string(),
+                video_key: url_decode(&key),
+                thumbnail_key: Some(url_decode(&thumbnail_key)),
+                title: url_decode(title),
+                lang: lang.to_string(),
+                subtitles: vec![],
+                keywords,
+            }).await?;
         }
     }
 
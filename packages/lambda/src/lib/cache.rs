@@ -0,0 +1,220 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tantivy::directory::{Directory, RamDirectory};
+use tantivy::tokenizer::TokenizerManager;
+use tantivy::{Index, IndexReader, ReloadPolicy};
+use crate::storage::ObjectStore;
+
+// how long replicas serve the loaded snapshots before asking the store for newer versions.
+const SNAPSHOT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone)]
+pub struct CachedIndex {
+    pub index: Index,
+    pub reader: IndexReader,
+    // directory the index link pointed to when it was opened, or the snapshot version of a replica.
+    source: String,
+}
+
+#[derive(Default)]
+struct CacheState {
+    indexes: HashMap<String, CachedIndex>,
+    tokenizers: HashMap<String, TokenizerManager>,
+    snapshot_checked: Option<Instant>,
+}
+
+impl CacheState {
+    // tokenizers are created once per language and shared by every index opened later.
+    fn use_tokenizers(&mut self, index: &mut Index, name: &str) -> anyhow::Result<()> {
+        match self.tokenizers.get(name) {
+            Some(tokenizers) => index.set_tokenizers(tokenizers.clone()),
+            None => {
+                crate::tantivy::register_tokenizers(index, name)?;
+                self.tokenizers.insert(name.to_string(), index.tokenizers().clone());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// keeps opened indexes and their readers across invocations. readers reload on commit and an index
+// is reopened when its link is switched to a rebuilt or restored directory. a replica serves the
+// latest snapshots loaded into memory instead of the mount.
+pub struct IndexCache {
+    mount: String,
+    replica: bool,
+    state: Mutex<CacheState>,
+}
+
+impl IndexCache {
+    pub fn new(mount: &str) -> Self {
+        IndexCache { mount: mount.to_string(), replica: false, state: Mutex::new(CacheState::default()) }
+    }
+
+    pub fn replica(mount: &str) -> Self {
+        IndexCache { mount: mount.to_string(), replica: true, state: Mutex::new(CacheState::default()) }
+    }
+
+    // `INDEX_SOURCE=snapshot` makes a replica.
+    pub fn from_env(mount: &str) -> Self {
+        match dotenv::var("INDEX_SOURCE").as_deref() {
+            Ok("snapshot") => Self::replica(mount),
+            _ => Self::new(mount),
+        }
+    }
+
+    pub fn mount(&self) -> &str {
+        &self.mount
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.replica
+    }
+
+    // the language index, or the video index for `VIDEO_INDEX`.
+    pub fn get(&self, name: &str) -> anyhow::Result<CachedIndex> {
+        let mut state = self.state.lock().unwrap();
+        if self.replica {
+            return state.indexes.get(name)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("no snapshot of {} is loaded", name));
+        }
+
+        let source = self.link_target(name);
+        if let Some(cached) = state.indexes.get(name).filter(|c| source.as_ref() == Some(&c.source)) {
+            return Ok(cached.clone());
+        }
+
+        let mut index = if name == crate::tantivy::VIDEO_INDEX {
+            crate::tantivy::tantivy_video_index(&self.mount)?
+        } else {
+            crate::tantivy::tantivy_index_without_tokenizers(&self.mount, name)?
+        };
+        state.use_tokenizers(&mut index, name)?;
+        let reader = index.reader_builder()
+            .reload_policy(ReloadPolicy::OnCommit)
+            .try_into()?;
+
+        let cached = CachedIndex { index, reader, source: self.link_target(name).unwrap_or_default() };
+        state.indexes.insert(name.to_string(), cached.clone());
+
+        Ok(cached)
+    }
+
+    // languages that can be searched.
+    pub fn langs(&self) -> anyhow::Result<Vec<String>> {
+        if !self.replica {
+            return crate::tantivy::indexed_langs(&self.mount);
+        }
+
+        let mut langs = self.state.lock().unwrap().indexes.keys()
+            .filter(|name| !name.starts_with('_'))
+            .cloned()
+            .collect::<Vec<_>>();
+        langs.sort();
+
+        Ok(langs)
+    }
+
+    fn link_target(&self, name: &str) -> Option<String> {
+        fs::canonicalize(Path::new(&self.mount).join(name))
+            .ok()
+            .map(|path| path.to_string_lossy().to_string())
+    }
+
+    pub fn has_snapshots(&self) -> bool {
+        !self.state.lock().unwrap().indexes.is_empty()
+    }
+
+    // loads snapshots newer than the served ones into memory, at most once per interval. a failed
+    // attempt is logged and waits for the next interval too, the loaded snapshots stay in service.
+    pub async fn refresh_snapshots(&self, store: &dyn ObjectStore) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.snapshot_checked.map_or(false, |c| c.elapsed() < SNAPSHOT_CHECK_INTERVAL) {
+                return;
+            }
+            state.snapshot_checked = Some(Instant::now());
+        }
+
+        let names = match crate::snapshot::snapshot_names(store).await {
+            Ok(names) => names,
+            Err(e) => {
+                println!("failed to list snapshots: {:?}", e);
+                return;
+            }
+        };
+        for name in names {
+            if let Err(e) = self.load_snapshot(store, &name).await {
+                println!("failed to load snapshot of {}: {:?}", name, e);
+            }
+        }
+    }
+
+    async fn load_snapshot(&self, store: &dyn ObjectStore, name: &str) -> anyhow::Result<()> {
+        let manifest = crate::snapshot::get_manifest(store, name, None).await?;
+        let served = self.state.lock().unwrap().indexes.get(name).map(|c| c.source.clone());
+        if served.as_deref() == Some(manifest.version.as_str()) {
+            return Ok(());
+        }
+
+        // the archive stays on disk while it is read, only the files of the index are held in memory.
+        crate::storage::check_name(&manifest.version)?;
+        let archive = std::env::temp_dir().join(format!("{}-{}.tar", name, manifest.version));
+        let dir = RamDirectory::create();
+        crate::snapshot::with_archive(store, &manifest, &archive, |archive| {
+            crate::snapshot::read_verified_files(archive, &manifest, |file, reader| {
+                let mut bytes = vec![];
+                reader.read_to_end(&mut bytes)?;
+                dir.atomic_write(Path::new(file), &bytes)?;
+                Ok(())
+            })
+        }).await?;
+        let mut index = Index::open(dir)?;
+
+        let mut state = self.state.lock().unwrap();
+        state.use_tokenizers(&mut index, name)?;
+        // a snapshot never changes, newer versions replace the whole index.
+        let reader = index.reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        println!("serve snapshot {} of {}", manifest.version, name);
+        state.indexes.insert(name.to_string(), CachedIndex { index, reader, source: manifest.version });
+
+        Ok(())
+    }
+}
+
+// refreshes the snapshots of a replica off the request path, once per interval. the task only runs
+// while the function is invoked, requests keep serving the loaded snapshots meanwhile.
+pub fn spawn_snapshot_refresh(indexes: Arc<IndexCache>, store: Arc<dyn ObjectStore>) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(SNAPSHOT_CHECK_INTERVAL).await;
+            indexes.refresh_snapshots(store.as_ref()).await;
+        }
+    });
+}
\ No newline at end of file
@@ -0,0 +1,323 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+ 
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use async_trait::async_trait;
+use aws_sdk_dynamodb::model::{AttributeValue, KeysAndAttributes};
+use aws_sdk_dynamodb::types::SdkError;
+use serde::{Deserialize, Serialize};
+use serde_dynamo::{from_item, from_items, to_attribute_value, to_item};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct VideoItem {
+    pub id: String,
+    pub title: String,
+    pub lang: String,
+    #[serde(default)]
+    pub subtitles: Vec<String>,
+    pub video_key: String,
+    #[serde(default)]
+    pub thumbnail_key: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+// the uploaded videos and the languages of their subtitles.
+#[async_trait]
+pub trait VideoCatalogue: Send + Sync {
+    async fn put_video(&self, video: &VideoItem) -> anyhow::Result<()>;
+    async fn find_video(&self, id: &str) -> anyhow::Result<Option<VideoItem>>;
+    // missing videos are left out.
+    async fn batch_get_videos(&self, ids: &[String]) -> anyhow::Result<Vec<VideoItem>>;
+    async fn scan_videos(&self) -> anyhow::Result<Vec<VideoItem>>;
+    // deleting a missing video succeeds.
+    async fn delete_video(&self, id: &str) -> anyhow::Result<()>;
+    // false when the video doesn't exist, it is never created by this.
+    async fn set_subtitles(&self, id: &str, subtitles: &[String]) -> anyhow::Result<bool>;
+}
+
+// `STORAGE_ROOT` keeps the catalogue in a local file, otherwise it is the table of `DYNAMODB_TABLE_NAME`.
+pub async fn catalogue_from_env() -> Box<dyn VideoCatalogue> {
+    if let Ok(root) = dotenv::var("STORAGE_ROOT") {
+        return Box::new(FileVideoCatalogue::new(Path::new(&root).join("catalogue.json")));
+    }
+
+    let table_name = dotenv::var("DYNAMODB_TABLE_NAME")
+        .expect("DYNAMODB_TABLE_NAME must be set.");
+    let shared_config = aws_config::from_env().load().await;
+    Box::new(DynamoVideoCatalogue::new(aws_sdk_dynamodb::Client::new(&shared_config), &table_name))
+}
+
+// lists the language in the subtitles of the video, which is returned as it was before. none when
+// the video doesn't exist.
+pub async fn add_subtitle(catalogue: &dyn VideoCatalogue, id: &str, lang: &str) -> anyhow::Result<Option<VideoItem>> {
+    let video = match catalogue.find_video(id).await? {
+        Some(video) => video,
+        None => return Ok(None),
+    };
+
+    if !video.subtitles.iter().any(|s| s == lang) {
+        let mut subtitles = video.subtitles.clone();
+        subtitles.push(lang.to_string());
+        if !catalogue.set_subtitles(id, &subtitles).await? {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(video))
+}
+
+// inverse of `add_subtitle`. returns whether the language was listed.
+pub async fn remove_subtitle(catalogue: &dyn VideoCatalogue, id: &str, lang: &str) -> anyhow::Result<bool> {
+    let video = match catalogue.find_video(id).await? {
+        Some(video) if video.subtitles.iter().any(|s| s == lang) => video,
+        _ => return Ok(false),
+    };
+    let subtitles = video.subtitles.into_iter()
+        .filter(|s| s != lang)
+        .collect::<Vec<_>>();
+
+    catalogue.set_subtitles(id, &subtitles).await
+}
+
+pub struct DynamoVideoCatalogue {
+    client: aws_sdk_dynamodb::Client,
+    table_name: String,
+}
+
+impl DynamoVideoCatalogue {
+    pub fn new(client: aws_sdk_dynamodb::Client, table_name: &str) -> Self {
+        DynamoVideoCatalogue { client, table_name: table_name.to_string() }
+    }
+
+    fn key(id: &str) -> HashMap<String, AttributeValue> {
+        HashMap::from([("id".to_string(), AttributeValue::S(id.to_owned()))])
+    }
+}
+
+#[async_trait]
+impl VideoCatalogue for DynamoVideoCatalogue {
+    async fn put_video(&self, video: &VideoItem) -> anyhow::Result<()> {
+        let item: HashMap<String, AttributeValue> = to_item(video)?;
+        self.client.put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn find_video(&self, id: &str) -> anyhow::Result<Option<VideoItem>> {
+        let output = self.client.get_item()
+            .table_name(&self.table_name)
+            .set_key(Some(Self::key(id)))
+            .send()
+            .await?;
+
+        Ok(output.item.map(from_item).transpose()?)
+    }
+
+    async fn batch_get_videos(&self, ids: &[String]) -> anyhow::Result<Vec<VideoItem>> {
+        let mut videos: Vec<VideoItem> = vec![];
+        // BatchGetItem accepts up to 100 keys per request.
+        for chunk in ids.chunks(100) {
+            let output = self.client.batch_get_item()
+                .request_items(&self.table_name, KeysAndAttributes::builder()
+                    .set_keys(Some(chunk.iter().map(|id| Self::key(id)).collect()))
+                    .build())
+                .send()
+                .await?;
+
+            if let Some(found) = output.responses.and_then(|mut r| r.remove(&self.table_name)) {
+                let found: Vec<VideoItem> = from_items(found)?;
+                videos.extend(found);
+            }
+        }
+
+        Ok(videos)
+    }
+
+    async fn scan_videos(&self) -> anyhow::Result<Vec<VideoItem>> {
+        let mut videos: Vec<VideoItem> = vec![];
+        let mut start_key = None;
+        loop {
+            let output = self.client.scan()
+                .table_name(&self.table_name)
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await?;
+
+            let found: Vec<VideoItem> = from_items(output.items.unwrap_or_default())?;
+            videos.extend(found);
+
+            start_key = output.last_evaluated_key;
+            if start_key.is_none() {
+                break;
+            }
+        }
+
+        Ok(videos)
+    }
+
+    async fn delete_video(&self, id: &str) -> anyhow::Result<()> {
+        self.client.delete_item()
+            .table_name(&self.table_name)
+            .set_key(Some(Self::key(id)))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn set_subtitles(&self, id: &str, subtitles: &[String]) -> anyhow::Result<bool> {
+        // without the condition an update of a deleted video creates a stub item, which fails to
+        // deserialize as a `VideoItem` and breaks the scan of the catalogue.
+        let result = self.client.update_item()
+            .table_name(&self.table_name)
+            .set_key(Some(Self::key(id)))
+            .update_expression("SET subtitles = :subtitles")
+            .condition_expression("attribute_exists(id)")
+            .expression_attribute_values(":subtitles", to_attribute_value(subtitles)?)
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(SdkError::ServiceError(e)) if e.err().is_conditional_check_failed_exception() => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// keeps the catalogue as one json file, for running without DynamoDB. the file is replaced on
+// every change, so it is only meant for a handful of videos.
+pub struct FileVideoCatalogue {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileVideoCatalogue {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileVideoCatalogue { path: path.into(), lock: Mutex::new(()) }
+    }
+
+    fn load(&self) -> anyhow::Result<BTreeMap<String, VideoItem>> {
+        match fs::read_to_string(&self.path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut BTreeMap<String, VideoItem>)) -> anyhow::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+        let mut videos = self.load()?;
+        f(&mut videos);
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(&videos)?)?;
+        fs::rename(tmp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl VideoCatalogue for FileVideoCatalogue {
+    async fn put_video(&self, video: &VideoItem) -> anyhow::Result<()> {
+        self.update(|videos| {
+            videos.insert(video.id.clone(), video.clone());
+        })
+    }
+
+    async fn find_video(&self, id: &str) -> anyhow::Result<Option<VideoItem>> {
+        Ok(self.load()?.remove(id))
+    }
+
+    async fn batch_get_videos(&self, ids: &[String]) -> anyhow::Result<Vec<VideoItem>> {
+        let mut videos = self.load()?;
+        Ok(ids.iter().filter_map(|id| videos.remove(id)).collect())
+    }
+
+    async fn scan_videos(&self) -> anyhow::Result<Vec<VideoItem>> {
+        Ok(self.load()?.into_values().collect())
+    }
+
+    async fn delete_video(&self, id: &str) -> anyhow::Result<()> {
+        self.update(|videos| {
+            videos.remove(id);
+        })
+    }
+
+    async fn set_subtitles(&self, id: &str, subtitles: &[String]) -> anyhow::Result<bool> {
+        let mut found = false;
+        self.update(|videos| {
+            if let Some(video) = videos.get_mut(id) {
+                video.subtitles = subtitles.to_vec();
+                found = true;
+            }
+        })?;
+
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::catalogue::{add_subtitle, FileVideoCatalogue, remove_subtitle, VideoCatalogue, VideoItem};
+    use crate::test_dir::TestDir;
+
+    #[tokio::test]
+    async fn file_catalogue_test() {
+        let dir = TestDir::new("catalogue-test");
+        let catalogue = FileVideoCatalogue::new(dir.path().join("catalogue.json"));
+        let video = VideoItem {
+            id: "a".to_string(),
+            title: "title".to_string(),
+            lang: "en-US".to_string(),
+            subtitles: vec!["en".to_string()],
+            ..Default::default()
+        };
+        catalogue.put_video(&video).await.unwrap();
+
+        assert_eq!(add_subtitle(&catalogue, "a", "ko").await.unwrap(), Some(video));
+        assert_eq!(add_subtitle(&catalogue, "b", "ko").await.unwrap(), None);
+        assert!(remove_subtitle(&catalogue, "a", "en").await.unwrap());
+        assert!(!remove_subtitle(&catalogue, "a", "en").await.unwrap());
+        assert_eq!(catalogue.find_video("a").await.unwrap().unwrap().subtitles, vec!["ko".to_string()]);
+        // a missing video is not created.
+        assert!(!catalogue.set_subtitles("b", &["ko".to_string()]).await.unwrap());
+        assert_eq!(catalogue.find_video("b").await.unwrap(), None);
+
+        let ids = ["b".to_string(), "a".to_string()];
+        assert_eq!(catalogue.batch_get_videos(&ids).await.unwrap().len(), 1);
+        catalogue.delete_video("a").await.unwrap();
+        catalogue.delete_video("a").await.unwrap();
+        assert!(catalogue.scan_videos().await.unwrap().is_empty());
+    }
+}
\ No newline at end of file
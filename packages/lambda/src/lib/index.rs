@@ -18,11 +18,40 @@
  
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexAction {
+    #[default]
+    Index,
+    // removes the documents of the video, from every language when `lang` is empty.
+    Delete,
+}
+
+#[derive(Default, Deserialize, Serialize)]
 pub struct IndexTopicMessage {
     pub video_id: String,
+    #[serde(default)]
     pub lang: String,
+    #[serde(default)]
     pub body: String,
+    #[serde(default)]
+    pub action: IndexAction,
+    // transcription confidence of every line of `body`.
+    #[serde(default)]
+    pub confidences: Vec<f32>,
+    // speaker of every line of `body`, empty when unknown.
+    #[serde(default)]
+    pub speakers: Vec<String>,
+    // upload time of the video in unix seconds.
+    #[serde(default)]
+    pub created_at: Option<i64>,
+}
+
+// `created_at` of the video items is written as `Utc::now().to_string()`.
+pub fn parse_created_at(created_at: &str) -> Option<i64> {
+    chrono::NaiveDateTime::parse_from_str(created_at.trim_end_matches(" UTC"), "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|t| t.and_utc().timestamp())
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -30,4 +59,62 @@ pub struct ImageFrameEvent{
     pub video_id: String,
     pub video_key: String,
     pub thumbnail_key: String
+}
+
+// where the image frame function puts the thumbnail of a video.
+pub fn thumbnail_key(video_id: &str) -> String {
+    format!("thumbnail/{}.jpg", video_id)
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct VideoDocument {
+    pub id: String,
+    pub title: String,
+    pub lang: String,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ReindexEvent {
+    // languages to rebuild, every language with stored subtitles when empty.
+    #[serde(default)]
+    pub langs: Vec<String>,
+    // discards the progress of a stopped run instead of resuming it.
+    #[serde(default)]
+    pub restart: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceAction {
+    #[default]
+    Optimize,
+    Stats,
+    Snapshot,
+    Restore,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MaintenanceEvent {
+    #[serde(default)]
+    pub action: MaintenanceAction,
+    // index names (languages or `_video`), every index on the mount when empty.
+    #[serde(default)]
+    pub indexes: Vec<String>,
+    // snapshot version to restore, the latest one when not set.
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::index::parse_created_at;
+
+    #[test]
+    fn parse_created_at_test() {
+        assert_eq!(parse_created_at("2023-03-01 10:20:30.123456789 UTC"), Some(1677666030));
+        assert_eq!(parse_created_at("2023-03-01 10:20:30 UTC"), Some(1677666030));
+        assert_eq!(parse_created_at("yesterday"), None);
+    }
 }
\ No newline at end of file
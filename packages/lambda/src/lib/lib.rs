@@ -18,4 +18,27 @@
  
 pub mod subtitle;
 pub mod index;
-pub mod tantivy;
\ No newline at end of file
+#[cfg(feature = "search")]
+pub mod tantivy;
+#[cfg(feature = "search")]
+pub mod search;
+pub mod synonym;
+pub mod translate;
+pub mod suggest;
+pub mod vector;
+#[cfg(feature = "search")]
+pub mod migration;
+#[cfg(feature = "search")]
+pub mod maintenance;
+#[cfg(feature = "search")]
+pub mod snapshot;
+#[cfg(feature = "search")]
+pub mod cache;
+#[cfg(feature = "search")]
+pub mod ranking;
+pub mod storage;
+pub mod catalogue;
+pub mod queue;
+pub mod search_event;
+#[cfg(test)]
+pub mod test_dir;
\ No newline at end of file
@@ -0,0 +1,106 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use tantivy::Index;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IndexStats {
+    pub name: String,
+    pub segments: usize,
+    pub docs: u64,
+    pub deleted_docs: u64,
+    pub size_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct OptimizeResult {
+    pub name: String,
+    pub merged_segments: usize,
+    pub deleted_files: usize,
+}
+
+// the language indexes followed by the video index.
+pub fn index_names(mount: &str) -> anyhow::Result<Vec<String>> {
+    let mut names = crate::tantivy::indexed_langs(mount)?;
+    if Path::new(mount).join(crate::tantivy::VIDEO_INDEX).join("meta.json").exists() {
+        names.push(crate::tantivy::VIDEO_INDEX.to_string());
+    }
+
+    Ok(names)
+}
+
+pub fn index_stats(mount: &str, name: &str) -> anyhow::Result<IndexStats> {
+    let index_path = Path::new(mount).join(name);
+    // stats only read the meta, so indexes of any schema version are opened as they are.
+    let index = Index::open_in_dir(&index_path)?;
+    let segment_metas = index.searchable_segment_metas()?;
+
+    let mut size_bytes = 0;
+    for entry in fs::read_dir(&index_path)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            size_bytes += metadata.len();
+        }
+    }
+
+    Ok(IndexStats {
+        name: name.to_string(),
+        segments: segment_metas.len(),
+        docs: segment_metas.iter().map(|m| m.num_docs() as u64).sum(),
+        deleted_docs: segment_metas.iter().map(|m| m.num_deleted_docs() as u64).sum(),
+        size_bytes,
+    })
+}
+
+pub fn all_index_stats(mount: &str) -> anyhow::Result<Vec<IndexStats>> {
+    index_names(mount)?
+        .iter()
+        .map(|name| index_stats(mount, name))
+        .collect()
+}
+
+// merges every segment into one, which also drops deleted documents, and removes the files no
+// longer used by the index.
+pub async fn optimize_index(mount: &str, name: &str) -> anyhow::Result<OptimizeResult> {
+    let index = if name == crate::tantivy::VIDEO_INDEX {
+        crate::tantivy::tantivy_video_index(mount)?
+    } else {
+        crate::tantivy::tantivy_index(mount, name)?
+    };
+    let mut index_writer = crate::tantivy::index_writer(&index).await?;
+
+    let segment_ids = index.searchable_segment_ids()?;
+    if segment_ids.len() > 1 {
+        index_writer.merge(&segment_ids).wait()?;
+    }
+    let gc = index_writer.garbage_collect_files().wait()?;
+    index_writer.wait_merging_threads()?;
+
+    for path in gc.failed_to_delete_files.iter() {
+        println!("failed to delete {} of {}", path.display(), name);
+    }
+
+    Ok(OptimizeResult {
+        name: name.to_string(),
+        merged_segments: if segment_ids.len() > 1 { segment_ids.len() } else { 0 },
+        deleted_files: gc.deleted_files.len(),
+    })
+}
\ No newline at end of file
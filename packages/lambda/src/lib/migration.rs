@@ -0,0 +1,396 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tantivy::schema::Schema;
+use tantivy::{Index, IndexWriter, Term};
+use crate::storage::ObjectStore;
+use crate::subtitle::{subtitle_key, Subtitle};
+use crate::tantivy::VideoCues;
+
+// indexes are built here and `{mount}/{lang}` links to the one in use, so a rebuild never
+// touches the index that is being searched.
+pub const BUILD_DIR: &str = "_build";
+// plain index directories from before the links are moved here on the first switch, and removed
+// once the link is in place.
+pub const RETIRED_DIR: &str = "_retired";
+// videos indexed between two commits. the reindex state is saved on every commit.
+const COMMIT_INTERVAL: usize = 100;
+
+// progress of a reindex, kept next to the index being built so a stopped run can resume.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReindexState {
+    pub build_path: String,
+    pub done: BTreeSet<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReindexProgress {
+    pub lang: String,
+    pub indexed: usize,
+    pub total: usize,
+    pub complete: bool,
+}
+
+// ids of the videos that have a `subtitle/{video_id}/{lang}.vtt` object.
+pub async fn list_subtitle_videos(objects: &dyn ObjectStore, lang: &str) -> anyhow::Result<Vec<String>> {
+    let video_ids = list_subtitles(objects).await?
+        .into_iter()
+        .filter(|(_, l)| l == lang)
+        .map(|(video_id, _)| video_id)
+        .collect();
+
+    Ok(video_ids)
+}
+
+pub async fn list_subtitle_langs(objects: &dyn ObjectStore) -> anyhow::Result<Vec<String>> {
+    let langs = list_subtitles(objects).await?
+        .into_iter()
+        .map(|(_, lang)| lang)
+        .collect::<BTreeSet<_>>();
+
+    Ok(langs.into_iter().collect())
+}
+
+// (video_id, lang) of every stored subtitle.
+async fn list_subtitles(objects: &dyn ObjectStore) -> anyhow::Result<Vec<(String, String)>> {
+    let subtitles = objects.list("subtitle/").await?
+        .iter()
+        .filter_map(|key| key.strip_prefix("subtitle/"))
+        .filter_map(|key| key.strip_suffix(".vtt"))
+        .filter_map(|key| key.split_once('/'))
+        .map(|(video_id, lang)| (video_id.to_string(), lang.to_string()))
+        .collect();
+
+    Ok(subtitles)
+}
+
+// the subtitle and the time it was stored in unix seconds, which stands in for the upload time of the video.
+pub async fn load_subtitle(objects: &dyn ObjectStore, video_id: &str, lang: &str) -> anyhow::Result<(Subtitle, i64)> {
+    let key = subtitle_key(video_id, lang);
+    let vtt = crate::storage::get_text(objects, &key).await?;
+    let last_modified = objects.head(&key).await?.last_modified;
+
+    Ok((Subtitle::from_vtt(&vtt)?, last_modified))
+}
+
+// an empty index of the current schema version in a new directory under `_build`.
+pub fn create_build_index(mount: &str, lang: &str) -> anyhow::Result<(String, Index)> {
+    let build_path = format!("{}/{}/{}-{}", mount, BUILD_DIR, lang, now_millis());
+    fs::create_dir_all(&build_path)?;
+    crate::tantivy::schema_version(&build_path)?;
+    let index = crate::tantivy::open_index(&build_path, lang)?;
+
+    Ok((build_path, index))
+}
+
+// points `{mount}/{lang}` to the built index. the link is replaced by a rename, so readers see
+// either the old or the new index, never a partial one. the previous index and stale builds of the
+// language are removed afterwards.
+pub fn switch_index(mount: &str, lang: &str, build_path: &str) -> anyhow::Result<()> {
+    let live_path = Path::new(mount).join(lang);
+    let link_path = Path::new(mount).join(format!("_{}.link", lang));
+    let target = Path::new(build_path).strip_prefix(mount)?;
+
+    let _ = fs::remove_file(&link_path);
+    std::os::unix::fs::symlink(target, &link_path)?;
+
+    let previous = match fs::read_link(&live_path) {
+        Ok(previous) => Some(Path::new(mount).join(previous)),
+        // a directory can't be replaced by a rename, so it is moved aside first.
+        Err(_) if live_path.is_dir() => {
+            let retired_path = Path::new(mount).join(RETIRED_DIR).join(format!("{}-{}", lang, now_millis()));
+            fs::create_dir_all(retired_path.parent().unwrap())?;
+            fs::rename(&live_path, &retired_path)?;
+            Some(retired_path)
+        }
+        Err(_) => None,
+    };
+    fs::rename(&link_path, &live_path)?;
+
+    if let Some(previous) = previous.filter(|p| p != Path::new(build_path) && p.exists()) {
+        println!("remove previous index of {} at {}", lang, previous.display());
+        fs::remove_dir_all(previous)?;
+    }
+
+    remove_stale_builds(mount, lang)
+}
+
+// removes the builds and retired directories of the language that neither the live link nor the
+// reindex state points to, e.g. builds of runs that were restarted.
+pub fn remove_stale_builds(mount: &str, lang: &str) -> anyhow::Result<()> {
+    let mut keep = BTreeSet::new();
+    if let Ok(target) = fs::read_link(Path::new(mount).join(lang)) {
+        keep.insert(Path::new(mount).join(target));
+    }
+    if let Some(state) = load_state(&state_path(mount, lang))? {
+        keep.insert(PathBuf::from(state.build_path));
+    }
+
+    for dir in [BUILD_DIR, RETIRED_DIR] {
+        let dir = Path::new(mount).join(dir);
+        if !dir.exists() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            // builds are named `{lang}-{millis}`.
+            let of_lang = path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(lang))
+                .and_then(|name| name.strip_prefix('-'))
+                .map_or(false, |millis| !millis.is_empty() && millis.chars().all(|c| c.is_ascii_digit()));
+            if of_lang && path.is_dir() && !keep.contains(&path) {
+                println!("remove stale index {}", path.display());
+                fs::remove_dir_all(&path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn state_path(mount: &str, lang: &str) -> PathBuf {
+    Path::new(mount).join(BUILD_DIR).join(format!("{}.reindex.json", lang))
+}
+
+// videos written to the live index while a reindex of the language is in progress, one id per line.
+pub fn replay_path(mount: &str, lang: &str) -> PathBuf {
+    Path::new(mount).join(BUILD_DIR).join(format!("{}.replay", lang))
+}
+
+// called by writers of the live index while they hold its writer lock. the reindex indexes the
+// videos again before it switches, as they may have changed after it listed the subtitles.
+pub fn record_replay<'a>(mount: &str, lang: &str, video_ids: impl Iterator<Item = &'a str>) -> anyhow::Result<()> {
+    if !state_path(mount, lang).exists() {
+        return Ok(());
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(replay_path(mount, lang))?;
+    for video_id in video_ids {
+        writeln!(file, "{}", video_id)?;
+    }
+
+    Ok(())
+}
+
+// builds a new index of the language from the subtitles in the bucket, continuing the build of a
+// stopped run when `resume` is set. the run stops early once `should_stop` returns true and the
+// index is switched over only when every video is indexed. the switch holds the writer lock of the
+// live index, so no message is written to it between the replay and the switch.
+pub async fn reindex(objects: &dyn ObjectStore,
+                     mount: &str,
+                     lang: &str,
+                     resume: bool,
+                     should_stop: &(dyn Fn() -> bool + Sync)) -> anyhow::Result<ReindexProgress> {
+    let state_path = state_path(mount, lang);
+    let (mut state, index) = match load_state(&state_path)? {
+        Some(state) if resume && Path::new(&state.build_path).exists() => {
+            println!("resume reindex of {} from {}", lang, state.build_path);
+            let index = crate::tantivy::open_index(&state.build_path, lang)?;
+            (state, index)
+        }
+        _ => {
+            let (build_path, index) = create_build_index(mount, lang)?;
+            let state = ReindexState { build_path, done: BTreeSet::new() };
+            // the state is saved before the listing, so writes to the live index after it are recorded.
+            let _ = fs::remove_file(replay_path(mount, lang));
+            save_state(&state_path, &state)?;
+            (state, index)
+        }
+    };
+    let video_ids = list_subtitle_videos(objects, lang).await?;
+
+    let schema = index.schema();
+    let mut index_writer = index.writer(crate::tantivy::INDEX_WRITER_HEAP)?;
+    let total = video_ids.len();
+    let remaining = video_ids.iter()
+        .filter(|id| !state.done.contains(*id))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut pending = 0;
+
+    for video_id in remaining.iter() {
+        if should_stop() {
+            break;
+        }
+
+        add_video(objects, &index_writer, &schema, lang, video_id).await?;
+        state.done.insert(video_id.clone());
+
+        pending += 1;
+        if pending == COMMIT_INTERVAL {
+            index_writer.commit()?;
+            save_state(&state_path, &state)?;
+            pending = 0;
+            println!("reindex {}: {}/{}", lang, state.done.len(), total);
+        }
+    }
+
+    let indexed = video_ids.iter().filter(|id| state.done.contains(*id)).count();
+    let complete = indexed == total;
+    let live_lock = if complete {
+        fs::create_dir_all(Path::new(mount).join(lang))?;
+        let live_lock = crate::tantivy::lock_index_dir(&Path::new(mount).join(lang)).await?;
+        let replayed = replay_videos(objects, mount, lang, &index_writer, &schema).await?;
+        println!("reindex {}: replayed {} videos", lang, replayed);
+        Some(live_lock)
+    } else {
+        None
+    };
+
+    index_writer.commit()?;
+    index_writer.wait_merging_threads()?;
+    save_state(&state_path, &state)?;
+
+    println!("reindex {}: {}/{}", lang, indexed, total);
+    if complete {
+        switch_index(mount, lang, &state.build_path)?;
+        fs::remove_file(&state_path)?;
+        let _ = fs::remove_file(replay_path(mount, lang));
+    }
+    drop(live_lock);
+
+    Ok(ReindexProgress { lang: lang.to_string(), indexed, total, complete })
+}
+
+async fn add_video(objects: &dyn ObjectStore, index_writer: &IndexWriter, schema: &Schema, lang: &str, video_id: &str) -> anyhow::Result<()> {
+    let video_id_field = schema.get_field("video_id").unwrap();
+    let (subtitle, created_at) = load_subtitle(objects, video_id, lang).await?;
+    let body = subtitle.index_body();
+    let cues = crate::tantivy::body_cues(&body);
+    // a video may be in the index without being in the state when a run stopped right after a commit.
+    index_writer.delete_term(Term::from_field_text(video_id_field, video_id));
+    crate::tantivy::add_cue_documents(index_writer, schema, &VideoCues {
+        video_id,
+        lang,
+        created_at,
+        cues: &cues,
+        confidences: &subtitle.index_confidences(),
+        speakers: &subtitle.index_speakers(),
+    })?;
+
+    Ok(())
+}
+
+// indexes the videos of `record_replay` again, or removes the ones whose subtitle is gone.
+async fn replay_videos(objects: &dyn ObjectStore, mount: &str, lang: &str, index_writer: &IndexWriter, schema: &Schema) -> anyhow::Result<usize> {
+    let video_ids = match fs::read_to_string(replay_path(mount, lang)) {
+        Ok(text) => text.lines().map(|l| l.to_string()).collect::<BTreeSet<_>>(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let video_id_field = schema.get_field("video_id").unwrap();
+    for video_id in video_ids.iter() {
+        let key = subtitle_key(video_id, lang);
+        if objects.list(&key).await?.contains(&key) {
+            add_video(objects, index_writer, schema, lang, video_id).await?;
+        } else {
+            index_writer.delete_term(Term::from_field_text(video_id_field, video_id));
+        }
+    }
+
+    Ok(video_ids.len())
+}
+
+fn load_state(path: &Path) -> anyhow::Result<Option<ReindexState>> {
+    match fs::read_to_string(path) {
+        Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_state(path: &Path, state: &ReindexState) -> anyhow::Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_string(state)?)?;
+    fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+pub fn now_millis() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use crate::migration::{record_replay, replay_path, state_path, switch_index};
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn switch_index_test() {
+        let dir = TestDir::new("migration-test");
+        let mount = dir.mount();
+        let build = |name: &str, meta: &str| {
+            let path = format!("{}/_build/{}", mount, name);
+            fs::create_dir_all(&path).unwrap();
+            fs::write(format!("{}/meta.json", path), meta).unwrap();
+            path
+        };
+        let live_meta = || fs::read_to_string(format!("{}/en/meta.json", mount)).unwrap();
+
+        // a plain directory from before the links, an abandoned build and a build of another language.
+        fs::create_dir_all(format!("{}/en", mount)).unwrap();
+        fs::write(format!("{}/en/meta.json", mount), "plain").unwrap();
+        let abandoned = build("en-1", "abandoned");
+        let other = build("de-1", "de");
+        let first = build("en-2", "first");
+
+        switch_index(mount, "en", &first).unwrap();
+        assert_eq!(live_meta(), "first");
+        assert_eq!(fs::read_link(format!("{}/en", mount)).unwrap(), Path::new("_build/en-2"));
+        assert!(!Path::new(&abandoned).exists());
+        assert!(Path::new(&other).exists());
+        assert_eq!(fs::read_dir(format!("{}/_retired", mount)).unwrap().count(), 0);
+
+        // from a link, the previous target is removed.
+        let second = build("en-3", "second");
+        switch_index(mount, "en", &second).unwrap();
+        assert_eq!(live_meta(), "second");
+        assert!(!Path::new(&first).exists());
+        assert!(!Path::new(&format!("{}/_en.link", mount)).exists());
+    }
+
+    #[test]
+    fn record_replay_test() {
+        let dir = TestDir::new("migration-test");
+        let mount = dir.mount();
+        fs::create_dir_all(format!("{}/_build", mount)).unwrap();
+
+        // nothing is recorded without a reindex in progress.
+        record_replay(mount, "en", ["a"].into_iter()).unwrap();
+        assert!(!replay_path(mount, "en").exists());
+
+        fs::write(state_path(mount, "en"), "{}").unwrap();
+        record_replay(mount, "en", ["a", "b"].into_iter()).unwrap();
+        record_replay(mount, "en", ["a"].into_iter()).unwrap();
+        assert_eq!(fs::read_to_string(replay_path(mount, "en")).unwrap(), "a\nb\na\n");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,290 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+ 
+use std::fmt::Display;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use async_trait::async_trait;
+use crate::storage::check_name;
+
+// a local function waits this long before it looks at an empty spool again.
+const SPOOL_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// messages handed to the next step of the pipeline. they are consumed by the functions
+// subscribed to the queue or topic, so only sending is part of the traits.
+#[async_trait]
+pub trait Queue: Send + Sync {
+    async fn send(&self, body: &str) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+pub trait Topic: Send + Sync {
+    async fn publish(&self, message: &str) -> anyhow::Result<()>;
+}
+
+// with `STORAGE_ROOT` set, messages are spooled under `{STORAGE_ROOT}/queues` and the functions running
+// locally take them from there. the variables of the queues and topics name the spools then, e.g.
+// `SUBTITLE_QUEUE_URL=subtitle`, so the sender and the consumer of a spool have to agree on its name.
+pub fn spool_from_env(var: &str) -> Option<SpoolQueue> {
+    let root = dotenv::var("STORAGE_ROOT").ok()?;
+    let name = dotenv::var(var)
+        .unwrap_or_else(|_| panic!("{} must be set.", var));
+    Some(SpoolQueue::new(Path::new(&root).join("queues"), &name)
+        .unwrap_or_else(|e| panic!("{} must name a spool: {}", var, e)))
+}
+
+// the queue of the url in `url_var`.
+pub async fn queue_from_env(url_var: &str) -> Box<dyn Queue> {
+    if let Some(spool) = spool_from_env(url_var) {
+        return Box::new(spool);
+    }
+
+    let queue_url = dotenv::var(url_var)
+        .unwrap_or_else(|_| panic!("{} must be set.", url_var));
+    let shared_config = aws_config::from_env().load().await;
+    Box::new(SqsQueue::new(aws_sdk_sqs::Client::new(&shared_config), &queue_url))
+}
+
+// the function named in `name_var`, invoked asynchronously with each message.
+pub async fn function_queue_from_env(name_var: &str) -> Box<dyn Queue> {
+    if let Some(spool) = spool_from_env(name_var) {
+        return Box::new(spool);
+    }
+
+    let function_name = dotenv::var(name_var)
+        .unwrap_or_else(|_| panic!("{} must be set.", name_var));
+    let shared_config = aws_config::from_env().load().await;
+    Box::new(FunctionQueue::new(aws_sdk_lambda::Client::new(&shared_config), &function_name))
+}
+
+// the topic of the arn in `arn_var`. locally a topic has a single spool, taken by the one function
+// subscribed to it.
+pub async fn topic_from_env(arn_var: &str) -> Box<dyn Topic> {
+    if let Some(spool) = spool_from_env(arn_var) {
+        return Box::new(spool);
+    }
+
+    let topic_arn = dotenv::var(arn_var)
+        .unwrap_or_else(|_| panic!("{} must be set.", arn_var));
+    let shared_config = aws_config::from_env().load().await;
+    Box::new(SnsTopic::new(aws_sdk_sns::Client::new(&shared_config), &topic_arn))
+}
+
+// takes the messages of the spool as they arrive, in place of the event source of a function.
+pub async fn run_spool<F, Fut, E>(spool: &SpoolQueue, mut handle: F) -> anyhow::Result<()>
+    where F: FnMut(String) -> Fut,
+          Fut: Future<Output = Result<(), E>>,
+          E: Display {
+    loop {
+        if drain_spool(spool, &mut handle).await? == 0 {
+            tokio::time::sleep(SPOOL_POLL_INTERVAL).await;
+        }
+    }
+}
+
+// hands every message of the spool to `handle` and returns how many there were. a failed message is
+// spooled again once the others are done, like a queue redelivers it.
+pub async fn drain_spool<F, Fut, E>(spool: &SpoolQueue, mut handle: F) -> anyhow::Result<usize>
+    where F: FnMut(String) -> Fut,
+          Fut: Future<Output = Result<(), E>>,
+          E: Display {
+    let mut count = 0;
+    let mut failures = vec![];
+    while let Some(body) = spool.receive()? {
+        count += 1;
+        if let Err(e) = handle(body.clone()).await {
+            println!("failed to handle a message of {}: {}", spool.dir.display(), e);
+            failures.push(body);
+        }
+    }
+    for body in failures {
+        spool.send(&body).await?;
+    }
+
+    Ok(count)
+}
+
+pub struct SqsQueue {
+    client: aws_sdk_sqs::Client,
+    queue_url: String,
+}
+
+impl SqsQueue {
+    pub fn new(client: aws_sdk_sqs::Client, queue_url: &str) -> Self {
+        SqsQueue { client, queue_url: queue_url.to_string() }
+    }
+}
+
+#[async_trait]
+impl Queue for SqsQueue {
+    async fn send(&self, body: &str) -> anyhow::Result<()> {
+        self.client.send_message()
+            .queue_url(&self.queue_url)
+            .message_body(body)
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
+
+// an asynchronous invocation is queued by Lambda, so a function takes messages like a queue.
+pub struct FunctionQueue {
+    client: aws_sdk_lambda::Client,
+    function_name: String,
+}
+
+impl FunctionQueue {
+    pub fn new(client: aws_sdk_lambda::Client, function_name: &str) -> Self {
+        FunctionQueue { client, function_name: function_name.to_string() }
+    }
+}
+
+#[async_trait]
+impl Queue for FunctionQueue {
+    async fn send(&self, body: &str) -> anyhow::Result<()> {
+        self.client.invoke()
+            .function_name(&self.function_name)
+            .invocation_type(aws_sdk_lambda::model::InvocationType::Event)
+            .payload(aws_sdk_lambda::types::Blob::new(body))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub struct SnsTopic {
+    client: aws_sdk_sns::Client,
+    topic_arn: String,
+}
+
+impl SnsTopic {
+    pub fn new(client: aws_sdk_sns::Client, topic_arn: &str) -> Self {
+        SnsTopic { client, topic_arn: topic_arn.to_string() }
+    }
+}
+
+#[async_trait]
+impl Topic for SnsTopic {
+    async fn publish(&self, message: &str) -> anyhow::Result<()> {
+        self.client.publish()
+            .topic_arn(&self.topic_arn)
+            .message(message)
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
+
+// messages kept as files of `{root}/{name}`, in place of a queue or topic. a message is written to a
+// temporary file first, so it is never taken half written, and taken by renaming it, so two
+// consumers never take the same message.
+pub struct SpoolQueue {
+    dir: PathBuf,
+}
+
+impl SpoolQueue {
+    pub fn new(root: impl AsRef<Path>, name: &str) -> anyhow::Result<Self> {
+        check_name(name)?;
+        let dir = root.as_ref().join(name);
+        fs::create_dir_all(&dir)?;
+
+        Ok(SpoolQueue { dir })
+    }
+
+    // takes the oldest message off the spool, `None` when it is empty.
+    pub fn receive(&self) -> anyhow::Result<Option<String>> {
+        let mut names = fs::read_dir(&self.dir)?
+            .map(|entry| entry.map(|e| e.file_name().to_string_lossy().to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        names.retain(|name| name.ends_with(".json"));
+        names.sort();
+
+        for name in names {
+            let path = self.dir.join(&name);
+            let taken = self.dir.join(format!("{}.taken", name));
+            match fs::rename(&path, &taken) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                result => result?,
+            }
+            let body = fs::read_to_string(&taken)?;
+            fs::remove_file(&taken)?;
+            return Ok(Some(body));
+        }
+
+        Ok(None)
+    }
+}
+
+#[async_trait]
+impl Queue for SpoolQueue {
+    async fn send(&self, body: &str) -> anyhow::Result<()> {
+        // names sort in the order the messages were sent.
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos();
+        let name = format!("{:020}-{}", nanos, uuid::Uuid::new_v4());
+        let tmp = self.dir.join(format!("{}.tmp", name));
+        fs::write(&tmp, body)?;
+        fs::rename(&tmp, self.dir.join(format!("{}.json", name)))?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Topic for SpoolQueue {
+    async fn publish(&self, message: &str) -> anyhow::Result<()> {
+        self.send(message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::queue::{drain_spool, Queue, SpoolQueue, Topic};
+    use crate::test_dir::TestDir;
+
+    #[tokio::test]
+    async fn spool_queue_test() {
+        let dir = TestDir::new("queue-test");
+        let root = dir.path();
+        assert!(SpoolQueue::new(root, "../subtitle").is_err());
+
+        let queue = SpoolQueue::new(root, "subtitle").unwrap();
+        queue.send("a").await.unwrap();
+        queue.publish("b").await.unwrap();
+        queue.send("c").await.unwrap();
+        // another handle of the same spool, e.g. in the function consuming it.
+        let consumer = SpoolQueue::new(root, "subtitle").unwrap();
+        assert_eq!(consumer.receive().unwrap().as_deref(), Some("a"));
+
+        let mut handled = vec![];
+        let count = drain_spool(&consumer, |body| {
+            handled.push(body.clone());
+            async move { if body == "b" { Err("failed") } else { Ok(()) } }
+        }).await.unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(handled, vec!["b", "c"]);
+        // the failed message is taken again.
+        assert_eq!(consumer.receive().unwrap().as_deref(), Some("b"));
+        assert_eq!(consumer.receive().unwrap(), None);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,124 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::collections::HashSet;
+use tantivy::collector::{Collector, TopDocs};
+use tantivy::fastfield::FastFieldReader;
+use tantivy::schema::Schema;
+use tantivy::{DocId, Score, SegmentReader};
+
+const SECONDS_PER_DAY: f32 = 86_400f32;
+
+// weights of the signals combined with the bm25 score of a cue:
+// - confidence: share of the score that depends on the transcription confidence, 0 ignores it.
+// - recency: boost of a video uploaded just now, halved every `recency_half_life_days`.
+// - title: boost of the cues of videos whose title matches the query too.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RankingWeights {
+    pub confidence: f32,
+    pub recency: f32,
+    pub recency_half_life_days: f32,
+    pub title: f32,
+}
+
+impl Default for RankingWeights {
+    fn default() -> Self {
+        RankingWeights {
+            confidence: 0.5,
+            recency: 0.2,
+            recency_half_life_days: 180f32,
+            title: 0.5,
+        }
+    }
+}
+
+impl RankingWeights {
+    // `RANK_CONFIDENCE_WEIGHT`, `RANK_RECENCY_WEIGHT`, `RANK_RECENCY_HALF_LIFE_DAYS` and `RANK_TITLE_WEIGHT`
+    // override the defaults. all of them at 0 ranks by bm25 only.
+    pub fn from_env() -> Self {
+        let env = |name: &str| dotenv::var(name).ok().and_then(|v| v.parse::<f32>().ok());
+        let default = Self::default();
+
+        RankingWeights {
+            confidence: env("RANK_CONFIDENCE_WEIGHT").unwrap_or(default.confidence).clamp(0f32, 1f32),
+            recency: env("RANK_RECENCY_WEIGHT").unwrap_or(default.recency).max(0f32),
+            recency_half_life_days: env("RANK_RECENCY_HALF_LIFE_DAYS").unwrap_or(default.recency_half_life_days).max(1f32),
+            title: env("RANK_TITLE_WEIGHT").unwrap_or(default.title).max(0f32),
+        }
+    }
+
+    // `age_days` is unknown for cues indexed without an upload time, they get no recency boost.
+    pub fn score(&self, bm25: Score, confidence: f32, age_days: Option<f32>, title_match: bool) -> Score {
+        let confidence = 1f32 - self.confidence * (1f32 - confidence.clamp(0f32, 1f32));
+        let recency = age_days
+            .map(|age| 1f32 + self.recency * 0.5f32.powf(age.max(0f32) / self.recency_half_life_days))
+            .unwrap_or(1f32);
+        let title = if title_match { 1f32 + self.title } else { 1f32 };
+
+        bm25 * confidence * recency * title
+    }
+}
+
+// the fast field of a video id, so the scorer can tell videos apart without loading documents.
+pub fn video_key(video_id: &str) -> u64 {
+    crate::vector::fnv1a(video_id.as_bytes())
+}
+
+// top cues by the bm25 score combined with `confidence`, `created_at` and `video_key` of the language schema.
+// `title_matches` holds the `video_key` of the videos whose title matches the query.
+pub fn top_cues(schema: &Schema, limit: usize, weights: RankingWeights, now: i64, title_matches: HashSet<u64>)
+    -> impl Collector<Fruit = Vec<(Score, tantivy::DocAddress)>> {
+    let confidence_field = schema.get_field("confidence").unwrap();
+    let created_at_field = schema.get_field("created_at").unwrap();
+    let video_key_field = schema.get_field("video_key").unwrap();
+
+    TopDocs::with_limit(limit).tweak_score(move |segment_reader: &SegmentReader| {
+        let fast_fields = segment_reader.fast_fields();
+        let confidence_reader = fast_fields.f64(confidence_field).unwrap();
+        let created_at_reader = fast_fields.i64(created_at_field).unwrap();
+        let video_key_reader = fast_fields.u64(video_key_field).unwrap();
+        let title_matches = title_matches.clone();
+
+        move |doc: DocId, bm25: Score| {
+            let created_at = created_at_reader.get(doc);
+            let age_days = (created_at > 0).then(|| (now - created_at) as f32 / SECONDS_PER_DAY);
+            let title_match = title_matches.contains(&video_key_reader.get(doc));
+
+            weights.score(bm25, confidence_reader.get(doc) as f32, age_days, title_match)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ranking::RankingWeights;
+
+    #[test]
+    fn score_test() {
+        let weights = RankingWeights { confidence: 0.5, recency: 1.0, recency_half_life_days: 10f32, title: 0.5 };
+
+        assert_eq!(weights.score(2f32, 1f32, None, false), 2f32);
+        assert_eq!(weights.score(2f32, 0f32, None, false), 1f32);
+        assert_eq!(weights.score(2f32, 1f32, Some(0f32), false), 4f32);
+        assert_eq!(weights.score(2f32, 1f32, Some(10f32), false), 3f32);
+        assert_eq!(weights.score(2f32, 1f32, None, true), 3f32);
+
+        let bm25_only = RankingWeights { confidence: 0f32, recency: 0f32, recency_half_life_days: 1f32, title: 0f32 };
+        assert_eq!(bm25_only.score(2f32, 0.3f32, Some(1f32), true), 2f32);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,1030 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::ops::Bound;
+use std::time::{Duration, SystemTime};
+use serde_json::{json, Map, Value};
+use tantivy::collector::{Count, DocSetCollector, FacetCollector, TopDocs};
+use tantivy::query::{BooleanQuery, BoostQuery, FuzzyTermQuery, MoreLikeThisQuery, Occur, Query, QueryParser, RangeQuery, TermQuery};
+use tantivy::schema::{Facet, Field, IndexRecordOption, Schema};
+use tantivy::{DocAddress, Index, Searcher, Term};
+use crate::cache::{CachedIndex, IndexCache};
+use crate::ranking::RankingWeights;
+pub use crate::search_event::{SearchAction, TantivySearchEvent};
+use crate::synonym::Synonyms;
+use crate::translate::Translator;
+use crate::vector::{Embedder, reciprocal_rank_fusion, RRF_K};
+
+pub struct LangQuery {
+    pub lang: String,
+    pub query: String,
+}
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 100;
+const DEFAULT_FUZZY_DISTANCE: u8 = 1;
+const MAX_FUZZY_DISTANCE: u8 = 2;
+// every translation has to finish well within the timeout of the search.
+const TRANSLATE_TIMEOUT: Duration = Duration::from_secs(2);
+// fuzzy matches rank below exact matches of the same term.
+const FUZZY_BOOST: f32 = 0.5;
+const WINDOW_BOOST: f32 = 0.5;
+const TITLE_BOOST: f32 = 2.0;
+// videos matching on title that boost their cues.
+const TITLE_MATCH_LIMIT: usize = 100;
+// similar cues collected before they are summed up per video.
+const RELATED_CUE_LIMIT: usize = 200;
+const MAX_CONTEXT: usize = 10;
+
+// (time, body) of a cue.
+type Cue = (String, String);
+
+#[derive(Debug)]
+pub enum SearchError {
+    InvalidQuery(String),
+    Index(anyhow::Error),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::InvalidQuery(reason) => write!(f, "InvalidQuery: {}", reason),
+            SearchError::Index(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+impl From<anyhow::Error> for SearchError {
+    fn from(e: anyhow::Error) -> Self {
+        SearchError::Index(e)
+    }
+}
+
+impl From<tantivy::TantivyError> for SearchError {
+    fn from(e: tantivy::TantivyError) -> Self {
+        SearchError::Index(e.into())
+    }
+}
+
+// runs the action of the event, for the search function and searches within the app.
+pub async fn execute(indexes: &IndexCache,
+                     event: &TantivySearchEvent,
+                     synonyms: &Synonyms,
+                     translator: &dyn Translator,
+                     embedder: Option<&dyn Embedder>) -> Result<Vec<Value>, SearchError> {
+    match event.action {
+        SearchAction::Suggest => {
+            let suggestions = crate::suggest::suggest(
+                indexes.mount(),
+                &event.lang,
+                event.video_id.as_deref(),
+                &event.query,
+                event.limit.unwrap_or(10))?;
+
+            return Ok(suggestions.into_iter()
+                .map(|s| serde_json::to_value(s).unwrap())
+                .collect());
+        }
+        SearchAction::Related => {
+            let video_id = event.video_id.as_deref()
+                .ok_or_else(|| SearchError::InvalidQuery("video_id must be set for related videos.".to_string()))?;
+            let related = related_videos(indexes, &event.lang, video_id, event.limit.unwrap_or(5))?;
+
+            return Ok(related.into_iter()
+                .map(|(video_id, score)| json!({ "video_id": video_id, "score": score }))
+                .collect());
+        }
+        SearchAction::Stats => {
+            let stats = crate::maintenance::all_index_stats(indexes.mount())?;
+            return Ok(stats.into_iter()
+                .map(|s| serde_json::to_value(s).unwrap())
+                .collect());
+        }
+        SearchAction::Facets => return facet_counts(indexes, event, synonyms),
+        SearchAction::Search => {}
+    }
+
+    let mut output = if event.cross_lingual {
+        let langs = if event.langs.is_empty() {
+            indexes.langs()?
+        } else {
+            event.langs.clone()
+        };
+        let queries = translate_queries(translator, event, &langs).await;
+        search_cross_lingual(indexes, event, &queries, synonyms)?
+    } else if let (true, Some(embedder)) = (event.semantic, embedder) {
+        hybrid_search(indexes, event, synonyms, embedder)?
+    } else {
+        search(indexes, event, synonyms)?
+    };
+
+    if let Some(context) = event.context {
+        add_context(indexes, &event.lang, &mut output, context)?;
+    }
+
+    Ok(output)
+}
+
+pub fn search(indexes: &IndexCache, event: &TantivySearchEvent, synonyms: &Synonyms) -> Result<Vec<Value>, SearchError> {
+    let hits = search_lang(indexes, &event.lang, &event.query, event, synonyms)?;
+    let mut output = hits.into_iter()
+        .map(|(score, mut value)| {
+            value["score"] = json!(score);
+            value
+        })
+        .collect::<Vec<_>>();
+
+    // library search blends video documents (title, keywords) with the cue hits. they have
+    // no facets or times, so they are left out of filtered searches.
+    if event.include_videos && event.video_id.is_none() && !event.has_cue_filters() {
+        let limit = event.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+        for (score, mut value) in search_videos(indexes, &synonyms.expand_query(&event.query), limit)? {
+            value["score"] = json!(score);
+            output.push(value);
+        }
+    }
+
+    Ok(output)
+}
+
+// fuses the keyword ranking with the nearest cues of the vector index. video documents of the
+// library search are ranked as a list of their own, so every score is on the same scale.
+pub fn hybrid_search(indexes: &IndexCache, event: &TantivySearchEvent, synonyms: &Synonyms, embedder: &dyn Embedder) -> Result<Vec<Value>, SearchError> {
+    if event.has_cue_filters() {
+        return search(indexes, event, synonyms);
+    }
+
+    let limit = event.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let keyword_hits = search(indexes, event, synonyms)?;
+    let vector_hits = crate::vector::search(indexes.mount(), &event.lang, event.video_id.as_deref(), &event.query, limit, embedder)?;
+
+    let mut values: HashMap<(String, String), Value> = HashMap::new();
+    let mut cue_ranking = vec![];
+    let mut video_ranking = vec![];
+    for value in keyword_hits {
+        let video_id = stored_text(&value, "video_id").unwrap_or_default().to_string();
+        let key = match stored_text(&value, "time") {
+            Some(time) => {
+                let key = (video_id, time.to_string());
+                cue_ranking.push(key.clone());
+                key
+            }
+            None => {
+                let key = (video_id, String::new());
+                video_ranking.push(key.clone());
+                key
+            }
+        };
+        values.insert(key, value);
+    }
+
+    let mut vector_ranking = vec![];
+    for hit in vector_hits {
+        let key = (hit.video_id.clone(), hit.time.clone());
+        values.entry(key.clone()).or_insert_with(|| json!({
+            "video_id": [hit.video_id],
+            "time": [hit.time],
+            "body": [hit.body],
+        }));
+        vector_ranking.push(key);
+    }
+
+    let fused = reciprocal_rank_fusion(&[cue_ranking, vector_ranking, video_ranking], RRF_K);
+    let output = fused.into_iter()
+        .take(limit)
+        .filter_map(|(key, score)| {
+            let mut value = values.remove(&key)?;
+            value["score"] = json!(score);
+            Some(value)
+        })
+        .collect();
+
+    Ok(output)
+}
+
+pub fn search_videos(indexes: &IndexCache, text: &str, limit: usize) -> Result<Vec<(f32, Value)>, SearchError> {
+    let schema = crate::tantivy::tantivy_video_schema();
+    let CachedIndex { index, reader: index_reader, .. } = indexes.get(crate::tantivy::VIDEO_INDEX)?;
+    let title_field = schema.get_field("title").unwrap();
+    let keywords_field = schema.get_field("keywords").unwrap();
+
+    let searcher = index_reader.searcher();
+    let mut query_parser = QueryParser::for_index(&index, vec![title_field, keywords_field]);
+    query_parser.set_field_boost(title_field, TITLE_BOOST);
+
+    let query = parse_user_query(&query_parser, text)?;
+    let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+
+    let mut output = vec![];
+    for (score, doc_address) in top_docs {
+        let doc = searcher.doc(doc_address)?;
+        let json_str = schema.to_json(&doc);
+        output.push((score, serde_json::from_str::<Value>(&json_str).unwrap()));
+    }
+
+    Ok(output)
+}
+
+// translations run concurrently, a language whose translation fails or times out is searched
+// with the query as it is.
+pub async fn translate_queries(translator: &dyn Translator, event: &TantivySearchEvent, langs: &[String]) -> Vec<LangQuery> {
+    let queries = langs.iter().map(|lang| async move {
+        if !event.translate || lang == &event.lang {
+            return LangQuery { lang: lang.clone(), query: event.query.clone() };
+        }
+
+        let translation = tokio::time::timeout(TRANSLATE_TIMEOUT, translator.translate(&event.query, &event.lang, lang)).await;
+        let query = match translation {
+            Ok(Ok(query)) => query,
+            Ok(Err(e)) => {
+                println!("failed to translate query into {}: {:?}", lang, e);
+                event.query.clone()
+            }
+            Err(_) => {
+                println!("translating query into {} timed out", lang);
+                event.query.clone()
+            }
+        };
+        LangQuery { lang: lang.clone(), query }
+    });
+
+    futures::future::join_all(queries).await
+}
+
+struct CrossLingualHit {
+    video_id: String,
+    time: String,
+    score: f32,
+    scores: Map<String, Value>,
+    // (lang, body, score) of the body shown for the hit.
+    body: (String, String, f32),
+}
+
+// searches every language index and merges hits of the same cue, which share the timestamp
+// because translated subtitles keep the timing of the original transcription.
+pub fn search_cross_lingual(indexes: &IndexCache, event: &TantivySearchEvent, queries: &[LangQuery], synonyms: &Synonyms) -> Result<Vec<Value>, SearchError> {
+    let no_synonyms = Synonyms::default();
+    let mut merged: HashMap<(String, String), CrossLingualHit> = HashMap::new();
+
+    for lang_query in queries {
+        let synonyms = if lang_query.lang == event.lang { synonyms } else { &no_synonyms };
+        let hits = match search_lang(indexes, &lang_query.lang, &lang_query.query, event, synonyms) {
+            Ok(hits) => hits,
+            // a translated query may lose every searchable word, the other languages still count.
+            Err(SearchError::InvalidQuery(reason)) if lang_query.lang != event.lang => {
+                println!("skip {}: {}", lang_query.lang, reason);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        for (score, value) in hits {
+            let (video_id, time, body) = match (stored_text(&value, "video_id"), stored_text(&value, "time"), stored_text(&value, "body")) {
+                (Some(video_id), Some(time), Some(body)) => (video_id.to_string(), time.to_string(), body.to_string()),
+                _ => continue,
+            };
+
+            let hit = merged.entry((video_id.clone(), time.clone()))
+                .or_insert_with(|| CrossLingualHit {
+                    video_id,
+                    time,
+                    score: 0f32,
+                    scores: Map::new(),
+                    body: (lang_query.lang.clone(), body.clone(), score),
+                });
+            hit.score += score;
+            hit.scores.insert(lang_query.lang.clone(), json!(score));
+
+            // prefer the body in the language of the query, otherwise the best scored one.
+            let (body_lang, _, body_score) = &hit.body;
+            if body_lang != &event.lang && (lang_query.lang == event.lang || score > *body_score) {
+                hit.body = (lang_query.lang.clone(), body, score);
+            }
+        }
+    }
+
+    let limit = event.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let mut hits = merged.into_values().collect::<Vec<_>>();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    hits.sort_by(|a, b| (&a.video_id, &a.time).cmp(&(&b.video_id, &b.time)));
+
+    let output = hits.into_iter()
+        .map(|hit| json!({
+            "video_id": [hit.video_id],
+            "time": [hit.time],
+            "body": [hit.body.1],
+            "lang": [hit.body.0],
+            "scores": hit.scores,
+        }))
+        .collect();
+
+    Ok(output)
+}
+
+// adds the surrounding cues of every hit as `before` and `after`, each `{"time", "body"}` in time order.
+// cross-lingual hits read the cues of the language their body is in.
+pub fn add_context(indexes: &IndexCache, lang: &str, hits: &mut [Value], size: usize) -> Result<(), SearchError> {
+    let size = size.min(MAX_CONTEXT);
+    if size == 0 {
+        return Ok(());
+    }
+
+    let mut cues: HashMap<(String, String), Vec<Cue>> = HashMap::new();
+    for hit in hits.iter_mut() {
+        let (video_id, time) = match (stored_text(hit, "video_id"), stored_text(hit, "time")) {
+            (Some(video_id), Some(time)) => (video_id.to_string(), time.to_string()),
+            _ => continue,
+        };
+        let hit_lang = stored_text(hit, "lang").unwrap_or(lang).to_string();
+
+        let key = (hit_lang, video_id);
+        if !cues.contains_key(&key) {
+            let video_cues = video_cues(indexes, &key.0, &key.1)?;
+            cues.insert(key.clone(), video_cues);
+        }
+
+        let (before, after) = context_window(&cues[&key], &time, size);
+        let to_json = |cues: &[Cue]| cues.iter()
+            .map(|(time, body)| json!({ "time": time, "body": body }))
+            .collect::<Vec<_>>();
+        hit["before"] = json!(to_json(before));
+        hit["after"] = json!(to_json(after));
+    }
+
+    Ok(())
+}
+
+// every cue of the video, ordered by time.
+fn video_cues(indexes: &IndexCache, lang: &str, video_id: &str) -> Result<Vec<Cue>, SearchError> {
+    let schema = crate::tantivy::tantivy_schema(lang);
+    let CachedIndex { reader: index_reader, .. } = indexes.get(lang)?;
+    let video_id_field = schema.get_field("video_id").unwrap();
+    let time_field = schema.get_field("time").unwrap();
+    let body_field = schema.get_field("body").unwrap();
+
+    let searcher = index_reader.searcher();
+    let video_query = TermQuery::new(
+        Term::from_field_text(video_id_field, video_id),
+        IndexRecordOption::Basic
+    );
+
+    let mut cues = vec![];
+    for doc_address in searcher.search(&video_query, &DocSetCollector)? {
+        let doc = searcher.doc(doc_address)?;
+        let text = |field: Field| doc.get_first(field).and_then(|v| v.as_text()).unwrap_or_default().to_string();
+        cues.push((text(time_field), text(body_field)));
+    }
+    // times are zero padded (hh:mm:ss.mmm), so they sort as strings.
+    cues.sort();
+
+    Ok(cues)
+}
+
+fn context_window<'a>(cues: &'a [Cue], time: &str, size: usize) -> (&'a [Cue], &'a [Cue]) {
+    match cues.binary_search_by(|(t, _)| t.as_str().cmp(time)) {
+        Ok(i) => (&cues[i.saturating_sub(size)..i], &cues[i + 1..(i + 1 + size).min(cues.len())]),
+        Err(_) => (&[], &[]),
+    }
+}
+
+fn stored_text<'a>(value: &'a Value, field: &str) -> Option<&'a str> {
+    value.get(field).and_then(|v| v.get(0)).and_then(|v| v.as_str())
+}
+
+fn search_lang(indexes: &IndexCache, lang: &str, text: &str, event: &TantivySearchEvent, synonyms: &Synonyms) -> Result<Vec<(f32, Value)>, SearchError> {
+    let schema = crate::tantivy::tantivy_schema(lang);
+    let CachedIndex { index, reader: index_reader, .. } = indexes.get(lang)?;
+    let query = cue_query(&index, &schema, text, event, synonyms)?;
+    let searcher = index_reader.searcher();
+
+    let limit = event.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let weights = RankingWeights::from_env();
+    // within one video every cue would get the same title boost.
+    let title_matches = if weights.title > 0f32 && event.video_id.is_none() {
+        title_matches(indexes, &synonyms.expand_query(text))
+    } else {
+        HashSet::new()
+    };
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs() as i64;
+    let collector = crate::ranking::top_cues(&schema, limit, weights, now, title_matches);
+    let top_docs = searcher.search(&query, &collector)?;
+    let body_query = text_query(&index, &schema, text, event, synonyms, false)?;
+    let top_docs = collapse_window_hits(&searcher, &schema, body_query.as_ref(), top_docs)?;
+
+    let mut output = vec![];
+    for (score, doc_address) in top_docs {
+        let doc = searcher.doc(doc_address)?;
+        let json_str = schema.to_json(&doc);
+        output.push((score, serde_json::from_str::<Value>(&json_str).unwrap()));
+    }
+
+    Ok(output)
+}
+
+// counts of every facet of the cues matching the query and filters of the event,
+// as `{"facet": "/speaker/spk_0", "count": 12}` per facet.
+pub fn facet_counts(indexes: &IndexCache, event: &TantivySearchEvent, synonyms: &Synonyms) -> Result<Vec<Value>, SearchError> {
+    let schema = crate::tantivy::tantivy_schema(&event.lang);
+    let CachedIndex { index, reader: index_reader, .. } = indexes.get(&event.lang)?;
+    let query = cue_query(&index, &schema, &event.query, event, synonyms)?;
+    let searcher = index_reader.searcher();
+
+    let mut output = vec![];
+    for name in crate::tantivy::FACET_FIELDS {
+        let root = Facet::from_path([name]);
+        let mut collector = FacetCollector::for_field(schema.get_field(name).unwrap());
+        collector.add_facet(root.clone());
+
+        let counts = searcher.search(&query, &collector)?;
+        for (facet, count) in counts.get(root) {
+            output.push(json!({ "facet": facet.to_string(), "count": count }));
+        }
+    }
+
+    Ok(output)
+}
+
+fn cue_query(index: &Index, schema: &Schema, text: &str, event: &TantivySearchEvent, synonyms: &Synonyms) -> Result<Box<dyn Query>, SearchError> {
+    let video_id_field = schema.get_field("video_id").unwrap();
+    let body_field = schema.get_field("body").unwrap();
+
+    let mut query = text_query(index, schema, text, event, synonyms, true)?;
+    if let Some(video_id) = event.video_id.as_ref() {
+        let video_query = TermQuery::new(
+            Term::from_field_text(video_id_field, video_id),
+            IndexRecordOption::Basic
+        );
+        query = Box::new(BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (Occur::Must, Box::new(video_query)),
+        ]));
+    }
+    if let Some(facet_query) = facet_filter(schema, &event.facets)? {
+        query = Box::new(BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (Occur::Must, facet_query),
+        ]));
+    }
+    if let Some(time_query) = time_filter(schema, event.after, event.before) {
+        query = Box::new(BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (Occur::Must, time_query),
+        ]));
+    }
+    if let Some(exclude_query) = exclude_filter(index, body_field, &event.exclude)? {
+        query = Box::new(BooleanQuery::new(vec![
+            (Occur::Must, query),
+            (Occur::MustNot, exclude_query),
+        ]));
+    }
+
+    Ok(query)
+}
+
+// the words of the query, without the filters of the event. `window` joins a cue with the next
+// one, so phrases split across two cues still match the earlier cue. it is only searched by
+// phrase queries and boosted lower, so a cue matching on its own body ranks first.
+fn text_query(index: &Index, schema: &Schema, text: &str, event: &TantivySearchEvent, synonyms: &Synonyms, window: bool) -> Result<Box<dyn Query>, SearchError> {
+    let body_field = schema.get_field("body").unwrap();
+    let window_field = schema.get_field("window").unwrap();
+
+    let expanded = synonyms.expand_query(text);
+    let mut fields = vec![body_field];
+    if window && expanded.contains('"') {
+        fields.push(window_field);
+    }
+    let mut query_parser = QueryParser::for_index(index, fields);
+    query_parser.set_field_boost(window_field, WINDOW_BOOST);
+
+    let mut query = parse_user_query(&query_parser, &expanded)?;
+    if event.fuzzy {
+        let distance = fuzzy_distance(event.fuzzy_distance);
+        let fuzzy = fuzzy_query(index, body_field, text, distance)?;
+        query = Box::new(BooleanQuery::new(vec![
+            (Occur::Should, query),
+            (Occur::Should, Box::new(BoostQuery::new(fuzzy, FUZZY_BOOST))),
+        ]));
+    }
+
+    Ok(query)
+}
+
+// drops the hits only matching on `window` whose next cue matches on its own body, the words are
+// all in that cue and it is the hit. the hits left over span two cues and stay on the earlier one.
+fn collapse_window_hits(searcher: &Searcher, schema: &Schema, body_query: &dyn Query, hits: Vec<(f32, DocAddress)>) -> Result<Vec<(f32, DocAddress)>, SearchError> {
+    let video_id_field = schema.get_field("video_id").unwrap();
+    let time_field = schema.get_field("time").unwrap();
+
+    let mut output = vec![];
+    for (score, doc_address) in hits {
+        if body_query.explain(searcher, doc_address).is_ok() {
+            output.push((score, doc_address));
+            continue;
+        }
+
+        let doc = searcher.doc(doc_address)?;
+        let text = |field: Field| doc.get_first(field).and_then(|v| v.as_text()).unwrap_or_default().to_string();
+        let (video_id, time) = (text(video_id_field), text(time_field));
+        let video_query: Box<dyn Query> = Box::new(TermQuery::new(
+            Term::from_field_text(video_id_field, &video_id),
+            IndexRecordOption::Basic
+        ));
+
+        // cues of the video starting after the hit, and before `upper`.
+        let later = |upper: Bound<&str>| -> Box<dyn Query> {
+            Box::new(BooleanQuery::new(vec![
+                (Occur::Must, video_query.box_clone()),
+                (Occur::Must, Box::new(RangeQuery::new_str_bounds(time_field, Bound::Excluded(time.as_str()), upper))),
+            ]))
+        };
+
+        // the earliest later cue matching on its own body. times are zero padded, so they sort as text.
+        let matching = BooleanQuery::new(vec![
+            (Occur::Must, body_query.box_clone()),
+            (Occur::Must, later(Bound::Unbounded)),
+        ]);
+        let mut first_match: Option<String> = None;
+        for match_address in searcher.search(&matching, &DocSetCollector)? {
+            let match_doc = searcher.doc(match_address)?;
+            let match_time = match_doc.get_first(time_field).and_then(|v| v.as_text()).unwrap_or_default();
+            if first_match.as_deref().map_or(true, |t| match_time < t) {
+                first_match = Some(match_time.to_string());
+            }
+        }
+
+        // it is the next cue when no other cue of the video starts in between.
+        let repeats_next = match first_match {
+            Some(match_time) => searcher.search(&later(Bound::Excluded(match_time.as_str())), &Count)? == 0,
+            None => false,
+        };
+        if !repeats_next {
+            output.push((score, doc_address));
+        }
+    }
+
+    Ok(output)
+}
+
+// cues starting in the range. cue times are zero padded, so they sort as text.
+fn time_filter(schema: &Schema, after: Option<u32>, before: Option<u32>) -> Option<Box<dyn Query>> {
+    if after.is_none() && before.is_none() {
+        return None;
+    }
+
+    let time_field = schema.get_field("time").unwrap();
+    let (after, before) = (after.map(cue_time), before.map(cue_time));
+    let lower = after.as_deref().map_or(Bound::Unbounded, Bound::Included);
+    let upper = before.as_deref().map_or(Bound::Unbounded, Bound::Excluded);
+
+    Some(Box::new(RangeQuery::new_str_bounds(time_field, lower, upper)))
+}
+
+// same format as the cue times of the index body.
+fn cue_time(seconds: u32) -> String {
+    format!("{:02}:{:02}:{:02}.000", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+// cues whose own body contains any of the words or phrases.
+fn exclude_filter(index: &Index, body_field: Field, exclude: &[String]) -> Result<Option<Box<dyn Query>>, SearchError> {
+    let query_parser = QueryParser::for_index(index, vec![body_field]);
+    let mut alternatives: Vec<(Occur, Box<dyn Query>)> = vec![];
+    for text in exclude.iter().map(|t| t.replace('"', " ")).filter(|t| !t.trim().is_empty()) {
+        let phrase = parse_user_query(&query_parser, &format!("\"{}\"", text.trim()))?;
+        alternatives.push((Occur::Should, phrase));
+    }
+    if alternatives.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(Box::new(BooleanQuery::new(alternatives))))
+}
+
+// facets of one field are alternatives, facets of different fields all have to match.
+// a facet also matches its children, e.g. `/uploaded` matches every upload month.
+fn facet_filter(schema: &Schema, facets: &[String]) -> Result<Option<Box<dyn Query>>, SearchError> {
+    let mut fields: BTreeMap<&str, Vec<(Occur, Box<dyn Query>)>> = BTreeMap::new();
+    for path in facets {
+        let facet = Facet::from_text(path)
+            .map_err(|_| SearchError::InvalidQuery(format!("invalid facet {}", path)))?;
+        let name = crate::tantivy::FACET_FIELDS.into_iter()
+            .find(|name| path.trim_start_matches('/').split('/').next() == Some(*name))
+            .ok_or_else(|| SearchError::InvalidQuery(format!("unknown facet {}", path)))?;
+        let term = Term::from_facet(schema.get_field(name).unwrap(), &facet);
+
+        fields.entry(name).or_default()
+            .push((Occur::Should, Box::new(TermQuery::new(term, IndexRecordOption::Basic))));
+    }
+    if fields.is_empty() {
+        return Ok(None);
+    }
+
+    let query = BooleanQuery::new(fields.into_values()
+        .map(|alternatives| (Occur::Must, Box::new(BooleanQuery::new(alternatives)) as Box<dyn Query>))
+        .collect());
+
+    Ok(Some(Box::new(query)))
+}
+
+// `video_key` of the videos whose title matches the text. the boost is optional, so a failing
+// video index only costs the boost.
+fn title_matches(indexes: &IndexCache, text: &str) -> HashSet<u64> {
+    let search_titles = || -> Result<HashSet<u64>, SearchError> {
+        let schema = crate::tantivy::tantivy_video_schema();
+        let CachedIndex { index, reader: index_reader, .. } = indexes.get(crate::tantivy::VIDEO_INDEX)?;
+        let video_id_field = schema.get_field("video_id").unwrap();
+        let title_field = schema.get_field("title").unwrap();
+
+        let searcher = index_reader.searcher();
+        let query = parse_user_query(&QueryParser::for_index(&index, vec![title_field]), text)?;
+
+        let mut keys = HashSet::new();
+        for (_, doc_address) in searcher.search(&query, &TopDocs::with_limit(TITLE_MATCH_LIMIT))? {
+            let doc = searcher.doc(doc_address)?;
+            if let Some(video_id) = doc.get_first(video_id_field).and_then(|v| v.as_text()) {
+                keys.insert(crate::ranking::video_key(video_id));
+            }
+        }
+
+        Ok(keys)
+    };
+
+    search_titles().unwrap_or_else(|e| {
+        println!("failed to match titles: {}", e);
+        HashSet::new()
+    })
+}
+
+// finds videos whose cues are like the aggregated body of the given video.
+pub fn related_videos(indexes: &IndexCache, lang: &str, video_id: &str, limit: usize) -> Result<Vec<(String, f32)>, SearchError> {
+    let schema = crate::tantivy::tantivy_schema(lang);
+    let CachedIndex { reader: index_reader, .. } = indexes.get(lang)?;
+    let video_id_field = schema.get_field("video_id").unwrap();
+    let body_field = schema.get_field("body").unwrap();
+
+    let searcher = index_reader.searcher();
+
+    let video_term = Term::from_field_text(video_id_field, video_id);
+    let video_query = TermQuery::new(video_term.clone(), IndexRecordOption::Basic);
+    let mut body = String::new();
+    for doc_address in searcher.search(&video_query, &DocSetCollector)? {
+        let doc = searcher.doc(doc_address)?;
+        if let Some(text) = doc.get_first(body_field).and_then(|v| v.as_text()) {
+            body.push_str(text);
+            body.push('\n');
+        }
+    }
+    if body.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let like_query = MoreLikeThisQuery::builder()
+        .with_min_doc_frequency(1)
+        .with_min_term_frequency(2)
+        .with_max_query_terms(30)
+        .with_document_fields(vec![(body_field, vec![tantivy::schema::Value::Str(body)])]);
+    let query = BooleanQuery::new(vec![
+        (Occur::Must, Box::new(like_query) as Box<dyn Query>),
+        (Occur::MustNot, Box::new(TermQuery::new(video_term, IndexRecordOption::Basic))),
+    ]);
+
+    let mut scores: HashMap<String, f32> = HashMap::new();
+    for (score, doc_address) in searcher.search(&query, &TopDocs::with_limit(RELATED_CUE_LIMIT))? {
+        let doc = searcher.doc(doc_address)?;
+        if let Some(id) = doc.get_first(video_id_field).and_then(|v| v.as_text()) {
+            *scores.entry(id.to_string()).or_insert(0f32) += score;
+        }
+    }
+
+    let mut related = scores.into_iter().collect::<Vec<_>>();
+    related.sort_by(|a, b| b.1.total_cmp(&a.1));
+    related.truncate(limit);
+
+    Ok(related)
+}
+
+pub fn parse_user_query(parser: &QueryParser, text: &str) -> Result<Box<dyn Query>, SearchError> {
+    // field syntax is not allowed from users, filters are applied as structured queries.
+    let text = text.replace(':', " ");
+    if text.trim().is_empty() {
+        return Err(SearchError::InvalidQuery("query is empty".to_string()));
+    }
+
+    if let Ok(query) = parser.parse_query(&text) {
+        return Ok(query);
+    }
+
+    // recover from broken syntax (stray quotes, brackets, dangling operators) by searching words only.
+    let words = plain_words(&text);
+    if words.is_empty() {
+        return Err(SearchError::InvalidQuery(
+            format!("`{}` has no searchable words", text.trim())));
+    }
+
+    parser.parse_query(&words)
+        .map_err(|e| SearchError::InvalidQuery(format!("{:?}", e)))
+}
+
+fn fuzzy_distance(distance: Option<u8>) -> u8 {
+    distance.unwrap_or(DEFAULT_FUZZY_DISTANCE).clamp(1, MAX_FUZZY_DISTANCE)
+}
+
+pub fn fuzzy_query(index: &Index, field: Field, text: &str, distance: u8) -> Result<Box<dyn Query>, SearchError> {
+    let analyzer = index.tokenizer_for_field(field)?;
+    let mut token_stream = analyzer.token_stream(&plain_words(text));
+
+    let mut subqueries: Vec<(Occur, Box<dyn Query>)> = vec![];
+    while token_stream.advance() {
+        let term = Term::from_field_text(field, &token_stream.token().text);
+        subqueries.push((Occur::Should, Box::new(FuzzyTermQuery::new(term, distance, true))));
+    }
+
+    Ok(Box::new(BooleanQuery::new(subqueries)))
+}
+
+fn plain_words(text: &str) -> String {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty() && !matches!(*w, "AND" | "OR" | "NOT" | "TO" | "IN"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::Index;
+    use tantivy::query::QueryParser;
+    use tantivy::collector::Count;
+    use tantivy::schema::Schema;
+    use tantivy::collector::TopDocs;
+    use crate::cache::IndexCache;
+    use crate::search::{collapse_window_hits, context_window, cue_query, cue_time, exclude_filter, facet_filter, fuzzy_distance, fuzzy_query, parse_user_query, plain_words, search_cross_lingual, stored_text, text_query, time_filter, translate_queries, LangQuery, SearchError, TantivySearchEvent, MAX_FUZZY_DISTANCE};
+    use crate::synonym::Synonyms;
+    use crate::translate::IdentityTranslator;
+    use crate::tantivy::VideoCues;
+    use crate::test_dir::TestDir;
+
+    // an english index with the cues of video `a`.
+    fn cue_index(cues: &[(&str, &str)]) -> (Schema, Index) {
+        let schema = crate::tantivy::tantivy_en_schema();
+        let index = Index::create_in_ram(schema.clone());
+        let mut index_writer = index.writer(crate::tantivy::INDEX_WRITER_HEAP).unwrap();
+        crate::tantivy::add_cue_documents(&index_writer, &schema, &VideoCues {
+            video_id: "a",
+            lang: "en",
+            created_at: 0,
+            cues,
+            confidences: &[],
+            speakers: &[],
+        }).unwrap();
+        index_writer.commit().unwrap();
+
+        (schema, index)
+    }
+
+    fn parser() -> QueryParser {
+        let schema = crate::tantivy::tantivy_en_schema();
+        let body_field = schema.get_field("body").unwrap();
+        let index = Index::create_in_ram(schema);
+        QueryParser::for_index(&index, vec![body_field])
+    }
+
+    #[test]
+    fn plain_words_test() {
+        assert_eq!(plain_words("\"free tier AND (lambda"), "free tier lambda");
+        assert_eq!(plain_words("k8s [node-group"), "k8s node group");
+    }
+
+    #[test]
+    fn parse_user_query_recovers_test() {
+        let parser = parser();
+
+        assert!(parse_user_query(&parser, "\"free tier").is_ok());
+        assert!(parse_user_query(&parser, "video_id:abc").is_ok());
+        assert!(matches!(parse_user_query(&parser, "  "), Err(SearchError::InvalidQuery(_))));
+        assert!(matches!(parse_user_query(&parser, "(\")"), Err(SearchError::InvalidQuery(_))));
+    }
+    #[test]
+    fn context_window_test() {
+        let cues = ["00:00:01.000", "00:00:02.000", "00:00:03.000", "00:01:00.000"].iter()
+            .map(|t| (t.to_string(), format!("cue {}", t)))
+            .collect::<Vec<_>>();
+
+        let (before, after) = context_window(&cues, "00:00:02.000", 2);
+        assert_eq!(before, &cues[0..1]);
+        assert_eq!(after, &cues[2..4]);
+
+        let (before, after) = context_window(&cues, "00:00:01.500", 2);
+        assert!(before.is_empty() && after.is_empty());
+    }
+
+    #[test]
+    fn facet_filter_test() {
+        let schema = crate::tantivy::tantivy_en_schema();
+        let index = Index::create_in_ram(schema.clone());
+        let mut index_writer = index.writer(crate::tantivy::INDEX_WRITER_HEAP).unwrap();
+        let cues = [("00:00:01.000", "hello"), ("00:00:02.000", "world"), ("00:00:03.000", "again")];
+        let speakers = ["spk_0", "spk_1", ""].map(String::from);
+        // 2023-03-01
+        crate::tantivy::add_cue_documents(&index_writer, &schema, &VideoCues {
+            video_id: "a",
+            lang: "en",
+            created_at: 1677666030,
+            cues: &cues,
+            confidences: &[],
+            speakers: &speakers,
+        }).unwrap();
+        index_writer.commit().unwrap();
+        let searcher = index.reader().unwrap().searcher();
+
+        let count = |facets: &[&str]| {
+            let facets = facets.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+            let query = facet_filter(&schema, &facets).unwrap().unwrap();
+            searcher.search(&query, &Count).unwrap()
+        };
+        assert_eq!(count(&["/speaker/spk_0"]), 1);
+        assert_eq!(count(&["/speaker/spk_0", "/speaker/spk_1"]), 2);
+        assert_eq!(count(&["/speaker/spk_1", "/uploaded/2023-03"]), 1);
+        assert_eq!(count(&["/speaker/spk_1", "/uploaded/2023-04"]), 0);
+        assert_eq!(count(&["/lang"]), 3);
+
+        assert!(facet_filter(&schema, &[]).unwrap().is_none());
+        assert!(matches!(facet_filter(&schema, &["/title/x".to_string()]), Err(SearchError::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn cue_filters_test() {
+        assert_eq!(cue_time(0), "00:00:00.000");
+        assert_eq!(cue_time(3725), "01:02:05.000");
+
+        let schema = crate::tantivy::tantivy_en_schema();
+        let body_field = schema.get_field("body").unwrap();
+        let index = Index::create_in_ram(schema.clone());
+        let mut index_writer = index.writer(crate::tantivy::INDEX_WRITER_HEAP).unwrap();
+        let cues = [("00:00:30.000", "free tier of lambda"), ("00:05:00.000", "the free tier"), ("01:00:00.000", "free credits")];
+        crate::tantivy::add_cue_documents(&index_writer, &schema, &VideoCues {
+            video_id: "a",
+            lang: "en",
+            created_at: 0,
+            cues: &cues,
+            confidences: &[],
+            speakers: &[],
+        }).unwrap();
+        index_writer.commit().unwrap();
+        let searcher = index.reader().unwrap().searcher();
+
+        let count_times = |after: Option<u32>, before: Option<u32>| {
+            searcher.search(&time_filter(&schema, after, before).unwrap(), &Count).unwrap()
+        };
+        assert_eq!(count_times(Some(300), None), 2);
+        assert_eq!(count_times(None, Some(300)), 1);
+        assert_eq!(count_times(Some(60), Some(3600)), 1);
+        assert!(time_filter(&schema, None, None).is_none());
+
+        let count_excluded = |exclude: &[&str]| {
+            let exclude = exclude.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+            searcher.search(&exclude_filter(&index, body_field, &exclude).unwrap().unwrap(), &Count).unwrap()
+        };
+        assert_eq!(count_excluded(&["lambda"]), 1);
+        assert_eq!(count_excluded(&["free tier"]), 2);
+        assert_eq!(count_excluded(&["tier free", "credits"]), 1);
+        assert!(exclude_filter(&index, body_field, &[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn fuzzy_query_test() {
+        let (schema, index) = cue_index(&[("00:00:01.000", "deploy with lambda")]);
+        let body_field = schema.get_field("body").unwrap();
+        let searcher = index.reader().unwrap().searcher();
+
+        let count = |text: &str, distance: u8| {
+            let query = fuzzy_query(&index, body_field, text, distance).unwrap();
+            searcher.search(&query, &Count).unwrap()
+        };
+        assert_eq!(count("lamda", 1), 1);
+        assert_eq!(count("lamda", 0), 0);
+        assert_eq!(count("lambda", 0), 1);
+
+        assert_eq!(fuzzy_distance(None), 1);
+        assert_eq!(fuzzy_distance(Some(0)), 1);
+        assert_eq!(fuzzy_distance(Some(5)), MAX_FUZZY_DISTANCE);
+    }
+
+    #[test]
+    fn window_hits_test() {
+        let (schema, index) = cue_index(&[
+            ("00:00:01.000", "lambda has a free"),
+            ("00:00:02.000", "tier of one million requests"),
+            ("00:00:03.000", "per month"),
+            ("00:00:04.000", "free tier"),
+        ]);
+        let time_field = schema.get_field("time").unwrap();
+        let searcher = index.reader().unwrap().searcher();
+        let synonyms = Synonyms::default();
+
+        let hit_times = |text: &str| {
+            let event: TantivySearchEvent = serde_json::from_value(serde_json::json!({ "lang": "en", "query": text })).unwrap();
+            let query = cue_query(&index, &schema, text, &event, &synonyms).unwrap();
+            let body_query = text_query(&index, &schema, text, &event, &synonyms, false).unwrap();
+            let hits = searcher.search(&query, &TopDocs::with_limit(10)).unwrap();
+            let mut times = collapse_window_hits(&searcher, &schema, body_query.as_ref(), hits).unwrap()
+                .into_iter()
+                .map(|(_, address)| searcher.doc(address).unwrap().get_first(time_field).unwrap().as_text().unwrap().to_string())
+                .collect::<Vec<_>>();
+            times.sort();
+            times
+        };
+        // the phrase spans the first two cues and maps back to the earlier one. a later cue matching
+        // on its own doesn't drop it unless it is the next cue.
+        assert_eq!(hit_times("\"free tier\""), vec!["00:00:01.000", "00:00:04.000"]);
+        assert_eq!(hit_times("\"requests per month\""), vec!["00:00:02.000"]);
+        // a phrase within one cue is not repeated by the window of the cue before.
+        assert_eq!(hit_times("\"one million\""), vec!["00:00:02.000"]);
+        assert_eq!(hit_times("\"one million\" lambda"), vec!["00:00:01.000", "00:00:02.000"]);
+        // single words never match the window.
+        assert_eq!(hit_times("tier"), vec!["00:00:02.000", "00:00:04.000"]);
+    }
+
+    #[tokio::test]
+    async fn search_cross_lingual_test() {
+        let dir = TestDir::new("search-test");
+        let mount = dir.mount();
+        let videos = [
+            ("en", "a", vec![("00:00:01.000", "the free tier"), ("00:00:02.000", "lambda pricing")]),
+            ("de", "a", vec![("00:00:01.000", "free tier ist kostenlos")]),
+            ("de", "b", vec![("00:00:05.000", "free")]),
+        ];
+        for (lang, video_id, cues) in videos.iter() {
+            let index = crate::tantivy::tantivy_index(mount, lang).unwrap();
+            let schema = index.schema();
+            let mut index_writer = index.writer(crate::tantivy::INDEX_WRITER_HEAP).unwrap();
+            crate::tantivy::add_cue_documents(&index_writer, &schema, &VideoCues {
+                video_id: *video_id,
+                lang: *lang,
+                created_at: 0,
+                cues,
+                confidences: &[],
+                speakers: &[],
+            }).unwrap();
+            index_writer.commit().unwrap();
+        }
+
+        let indexes = IndexCache::new(mount);
+        let synonyms = Synonyms::default();
+        let event: TantivySearchEvent = serde_json::from_value(serde_json::json!({
+            "lang": "en",
+            "query": "free tier",
+            "cross_lingual": true,
+            "translate": true,
+        })).unwrap();
+        let langs = vec!["de".to_string(), "en".to_string()];
+
+        let queries = translate_queries(&IdentityTranslator, &event, &langs).await;
+        assert_eq!(queries.iter().map(|q| (q.lang.as_str(), q.query.as_str())).collect::<Vec<_>>(),
+                   vec![("de", "free tier"), ("en", "free tier")]);
+
+        // the cue of `a` is merged across both languages and shows the english body.
+        let hits = search_cross_lingual(&indexes, &event, &queries, &synonyms).unwrap();
+        let summary = |hits: &[serde_json::Value]| hits.iter()
+            .map(|h| (
+                stored_text(h, "video_id").unwrap().to_string(),
+                stored_text(h, "lang").unwrap().to_string(),
+                h["scores"].as_object().unwrap().keys().cloned().collect::<Vec<_>>(),
+            ))
+            .collect::<Vec<_>>();
+        assert_eq!(summary(&hits), vec![
+            ("a".to_string(), "en".to_string(), vec!["de".to_string(), "en".to_string()]),
+            ("b".to_string(), "de".to_string(), vec!["de".to_string()]),
+        ]);
+        assert_eq!(stored_text(&hits[0], "body"), Some("the free tier"));
+
+        // a translation without searchable words skips its language, but not the language of the query.
+        let queries = vec![
+            LangQuery { lang: "de".to_string(), query: "\"(".to_string() },
+            LangQuery { lang: "en".to_string(), query: "free tier".to_string() },
+        ];
+        let hits = search_cross_lingual(&indexes, &event, &queries, &synonyms).unwrap();
+        assert_eq!(summary(&hits), vec![("a".to_string(), "en".to_string(), vec!["en".to_string()])]);
+
+        let queries = vec![LangQuery { lang: "en".to_string(), query: "\"(".to_string() }];
+        assert!(matches!(search_cross_lingual(&indexes, &event, &queries, &synonyms), Err(SearchError::InvalidQuery(_))));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,87 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchAction {
+    #[default]
+    Search,
+    Suggest,
+    Related,
+    Stats,
+    // counts of the facets of the hits, see `facet_counts`.
+    Facets,
+}
+
+impl SearchAction {
+    // actions matching the query against the cues, which expand it with synonyms.
+    pub fn uses_synonyms(&self) -> bool {
+        matches!(self, SearchAction::Search | SearchAction::Facets)
+    }
+}
+
+// the payload of the search function, also built by the app.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TantivySearchEvent {
+    #[serde(default)]
+    pub action: SearchAction,
+    pub lang: String,
+    pub query: String,
+    #[serde(default)]
+    pub video_id: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub fuzzy: bool,
+    #[serde(default)]
+    pub fuzzy_distance: Option<u8>,
+    #[serde(default)]
+    pub cross_lingual: bool,
+    #[serde(default)]
+    pub langs: Vec<String>,
+    #[serde(default)]
+    pub translate: bool,
+    #[serde(default)]
+    pub include_videos: bool,
+    #[serde(default)]
+    pub semantic: bool,
+    // number of cues returned before and after each hit.
+    #[serde(default)]
+    pub context: Option<usize>,
+    // facet filters, e.g. `/speaker/spk_0` or `/uploaded/2023-03`. a hit needs one of the given
+    // facets of every facet field.
+    #[serde(default)]
+    pub facets: Vec<String>,
+    // words and phrases a hit must not contain.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // start time range of the hits in seconds, `after` inclusive and `before` exclusive.
+    #[serde(default)]
+    pub after: Option<u32>,
+    #[serde(default)]
+    pub before: Option<u32>,
+}
+
+impl TantivySearchEvent {
+    // filters that only apply to cues, neither video documents nor vectors can be narrowed by them.
+    pub fn has_cue_filters(&self) -> bool {
+        !self.facets.is_empty() || !self.exclude.is_empty() || self.after.is_some() || self.before.is_some()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,335 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tantivy::Index;
+use crate::storage::ObjectStore;
+
+// snapshots of an index are kept as `snapshot/{name}/{version}.tar` with a `.json` manifest next to it.
+pub const SNAPSHOT_PREFIX: &str = "snapshot";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotManifest {
+    pub name: String,
+    pub version: String,
+    pub archive_sha256: String,
+    // sha256 of every file in the archive.
+    pub files: BTreeMap<String, String>,
+}
+
+pub fn archive_key(name: &str, version: &str) -> String {
+    format!("{}/{}/{}.tar", SNAPSHOT_PREFIX, name, version)
+}
+
+pub fn manifest_key(name: &str, version: &str) -> String {
+    format!("{}/{}/{}.json", SNAPSHOT_PREFIX, name, version)
+}
+
+// archives are streamed from and to files, so an index never has to fit into memory. this hashes
+// what is read or written through it on the way.
+struct Hashing<T> {
+    inner: T,
+    hasher: Sha256,
+}
+
+impl<T> Hashing<T> {
+    fn new(inner: T) -> Self {
+        Hashing { inner, hasher: Sha256::new() }
+    }
+
+    fn finish(self) -> (T, String) {
+        (self.inner, format!("{:x}", self.hasher.finalize()))
+    }
+}
+
+impl<R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Hashing<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    let mut reader = Hashing::new(File::open(path)?);
+    io::copy(&mut reader, &mut io::sink())?;
+
+    Ok(reader.finish().1)
+}
+
+// packs the files of the directory into the tar archive at `archive` and records their checksums.
+pub fn pack_files(dir: &Path, name: &str, version: &str, files: &[String], archive: &Path) -> anyhow::Result<SnapshotManifest> {
+    let mut builder = tar::Builder::new(Hashing::new(BufWriter::new(File::create(archive)?)));
+    let mut checksums = BTreeMap::new();
+    for file in files {
+        let path = dir.join(file);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(fs::metadata(&path)?.len());
+        header.set_mode(0o644);
+        header.set_cksum();
+        let mut reader = Hashing::new(File::open(&path)?);
+        builder.append_data(&mut header, file, &mut reader)?;
+        checksums.insert(file.clone(), reader.finish().1);
+    }
+    let (mut writer, archive_sha256) = builder.into_inner()?.finish();
+    writer.flush()?;
+
+    Ok(SnapshotManifest {
+        name: name.to_string(),
+        version: version.to_string(),
+        archive_sha256,
+        files: checksums,
+    })
+}
+
+// unpacks the archive into the directory after checking it against the manifest. the directory is
+// removed again unless the archive and every file in it match their checksums.
+pub fn unpack_files(archive: &Path, manifest: &SnapshotManifest, dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    let result = read_verified_files(archive, manifest, |file, reader| {
+        io::copy(reader, &mut BufWriter::new(File::create(dir.join(file))?))?;
+        Ok(())
+    });
+    if result.is_err() {
+        fs::remove_dir_all(dir)?;
+    }
+
+    result
+}
+
+// hands every file in the archive to `read`, checked against the manifest. a file is only known to
+// be intact once `read` has returned, so what it kept of a file is discarded on an error.
+pub fn read_verified_files(archive: &Path,
+                           manifest: &SnapshotManifest,
+                           mut read: impl FnMut(&str, &mut dyn Read) -> anyhow::Result<()>) -> anyhow::Result<()> {
+    if sha256_file(archive)? != manifest.archive_sha256 {
+        anyhow::bail!("checksum mismatch of snapshot {} {}", manifest.name, manifest.version);
+    }
+
+    let mut count = 0;
+    for entry in tar::Archive::new(File::open(archive)?).entries()? {
+        let entry = entry?;
+        let file = entry.path()?.to_string_lossy().to_string();
+        // never write outside of the directory.
+        if file.contains('/') || file.contains("..") {
+            anyhow::bail!("invalid file name {}", file);
+        }
+        let expected = manifest.files.get(&file)
+            .ok_or_else(|| anyhow::anyhow!("{} is not in the manifest", file))?;
+        let mut reader = Hashing::new(entry);
+        read(&file, &mut reader)?;
+        // what `read` left unread counts too.
+        io::copy(&mut reader, &mut io::sink())?;
+        if &reader.finish().1 != expected {
+            anyhow::bail!("checksum mismatch of {} in snapshot {} {}", file, manifest.name, manifest.version);
+        }
+        count += 1;
+    }
+    if count != manifest.files.len() {
+        anyhow::bail!("snapshot {} {} misses files", manifest.name, manifest.version);
+    }
+
+    Ok(())
+}
+
+// downloads the archive of the snapshot to `archive` and hands the file to `read`. the file is
+// removed afterwards.
+pub async fn with_archive<T>(store: &dyn ObjectStore,
+                             manifest: &SnapshotManifest,
+                             archive: &Path,
+                             read: impl FnOnce(&Path) -> anyhow::Result<T>) -> anyhow::Result<T> {
+    let result = match store.download(&archive_key(&manifest.name, &manifest.version), archive).await {
+        Ok(()) => read(archive),
+        Err(e) => Err(e),
+    };
+    if archive.exists() {
+        fs::remove_file(archive)?;
+    }
+
+    result
+}
+
+pub async fn put_snapshot(store: &dyn ObjectStore, archive: &Path, manifest: &SnapshotManifest) -> anyhow::Result<()> {
+    // the manifest is written last, so a listed version always has its archive.
+    store.upload(&archive_key(&manifest.name, &manifest.version), archive).await?;
+    store.put(&manifest_key(&manifest.name, &manifest.version), serde_json::to_vec(manifest)?).await?;
+
+    Ok(())
+}
+
+// names of the indexes that have snapshots.
+pub async fn snapshot_names(store: &dyn ObjectStore) -> anyhow::Result<Vec<String>> {
+    let names = store.list(&format!("{}/", SNAPSHOT_PREFIX)).await?
+        .iter()
+        .filter_map(|key| key.split('/').nth(1))
+        .map(|name| name.to_string())
+        .collect::<BTreeSet<_>>();
+
+    Ok(names.into_iter().collect())
+}
+
+// the manifest of the version, or of the latest snapshot of the index.
+pub async fn get_manifest(store: &dyn ObjectStore, name: &str, version: Option<&str>) -> anyhow::Result<SnapshotManifest> {
+    let key = match version {
+        Some(version) => manifest_key(name, version),
+        None => store.list(&format!("{}/{}/", SNAPSHOT_PREFIX, name)).await?
+            .into_iter()
+            .filter(|key| key.ends_with(".json"))
+            .max()
+            .ok_or_else(|| anyhow::anyhow!("no snapshot of {}", name))?,
+    };
+
+    Ok(serde_json::from_slice(&store.get(&key).await?)?)
+}
+
+// files of the last commit. writers are blocked while the lock is held, so none of them are
+// merged away or garbage collected before they are linked.
+fn committed_files(index: &Index, index_path: &Path) -> anyhow::Result<Vec<String>> {
+    let mut files = vec!["meta.json".to_string()];
+    if index_path.join(crate::tantivy::SCHEMA_VERSION_FILE).exists() {
+        files.push(crate::tantivy::SCHEMA_VERSION_FILE.to_string());
+    }
+    for segment_meta in index.searchable_segment_metas()? {
+        for path in segment_meta.list_files() {
+            if index_path.join(&path).exists() {
+                files.push(path.to_string_lossy().to_string());
+            }
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+// snapshots are staged next to the builds, on the file system of the index rather than in the small
+// temporary storage of the function. the `.snapshot-` names are never taken for builds.
+fn staging_path(mount: &str, name: &str, version: &str) -> anyhow::Result<std::path::PathBuf> {
+    crate::storage::check_name(version)?;
+
+    Ok(Path::new(mount).join(crate::migration::BUILD_DIR).join(format!("{}.snapshot-{}", name, version)))
+}
+
+// packs the committed state of the index and stores it as a new version.
+pub async fn create_snapshot(store: &dyn ObjectStore, mount: &str, name: &str) -> anyhow::Result<SnapshotManifest> {
+    let index_path = Path::new(mount).join(name);
+    let index = Index::open_in_dir(&index_path)?;
+    let version = format!("{:013}", crate::migration::now_millis());
+    let staging = staging_path(mount, name, &version)?;
+    let files_dir = staging.join("files");
+    fs::create_dir_all(&files_dir)?;
+
+    let result = async {
+        // segment files never change, so links taken while writers are blocked keep the committed
+        // state even if a merge removes the files from the index afterwards.
+        let index_writer = crate::tantivy::index_writer(&index).await?;
+        let files = committed_files(&index, &index_path)?;
+        for file in &files {
+            let (from, to) = (index_path.join(file), files_dir.join(file));
+            if fs::hard_link(&from, &to).is_err() {
+                fs::copy(&from, &to)?;
+            }
+        }
+        drop(index_writer);
+
+        let archive = staging.join("snapshot.tar");
+        let manifest = pack_files(&files_dir, name, &version, &files, &archive)?;
+        put_snapshot(store, &archive, &manifest).await?;
+
+        Ok::<_, anyhow::Error>(manifest)
+    }.await;
+    fs::remove_dir_all(&staging)?;
+
+    result
+}
+
+// unpacks a snapshot into a new directory under `_build` and switches the index over to it.
+pub async fn restore_snapshot(store: &dyn ObjectStore, mount: &str, name: &str, version: Option<&str>) -> anyhow::Result<SnapshotManifest> {
+    let manifest = get_manifest(store, name, version).await?;
+
+    let build_path = format!("{}/{}/{}-{}", mount, crate::migration::BUILD_DIR, name, crate::migration::now_millis());
+    let staging = staging_path(mount, name, &manifest.version)?;
+    fs::create_dir_all(&staging)?;
+    let result = with_archive(store, &manifest, &staging.join("snapshot.tar"), |archive| {
+        unpack_files(archive, &manifest, Path::new(&build_path))
+    }).await;
+    fs::remove_dir_all(&staging)?;
+    result?;
+    // fails on a broken meta before the restored index is switched to.
+    Index::open_in_dir(&build_path)?;
+    crate::migration::switch_index(mount, name, &build_path)?;
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::snapshot::{get_manifest, pack_files, put_snapshot, unpack_files, with_archive};
+    use crate::storage::{LocalObjectStore, ObjectStore};
+    use crate::test_dir::TestDir;
+
+    #[tokio::test]
+    async fn snapshot_round_trip_test() {
+        let dir = TestDir::new("snapshot-test");
+        let root = dir.path();
+        let index_path = root.join("index");
+        fs::create_dir_all(&index_path).unwrap();
+        fs::write(index_path.join("meta.json"), "{}").unwrap();
+        fs::write(index_path.join("a.store"), "store").unwrap();
+
+        let store = LocalObjectStore::new(root.join("bucket"));
+        let files = vec!["a.store".to_string(), "meta.json".to_string()];
+        for version in ["0000000000001", "0000000000002"] {
+            let archive = root.join(format!("{}.tar", version));
+            let manifest = pack_files(&index_path, "en", version, &files, &archive).unwrap();
+            put_snapshot(&store, &archive, &manifest).await.unwrap();
+        }
+
+        let manifest = get_manifest(&store, "en", None).await.unwrap();
+        assert_eq!(manifest.version, "0000000000002");
+
+        let archive = root.join("downloaded.tar");
+        with_archive(&store, &manifest, &archive, |archive| unpack_files(archive, &manifest, &root.join("restored"))).await.unwrap();
+        assert_eq!(fs::read_to_string(root.join("restored/a.store")).unwrap(), "store");
+        assert!(!archive.exists());
+
+        let mut bytes = store.get("snapshot/en/0000000000002.tar").await.unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        fs::write(&archive, bytes).unwrap();
+        assert!(unpack_files(&archive, &manifest, &root.join("broken")).is_err());
+        assert!(!root.join("broken").exists());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,307 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+ 
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use anyhow::bail;
+use async_trait::async_trait;
+use aws_sdk_s3::types::ByteStream;
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+// objects of the bucket: videos, transcriptions, subtitles, thumbnails and snapshots.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> anyhow::Result<()>;
+    async fn get(&self, key: &str) -> anyhow::Result<Vec<u8>>;
+    async fn list(&self, prefix: &str) -> anyhow::Result<Vec<String>>;
+    // deleting a missing object succeeds.
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+    async fn head(&self, key: &str) -> anyhow::Result<ObjectHead>;
+    // videos are copied between the store and a file without holding them in memory.
+    async fn download(&self, key: &str, path: &Path) -> anyhow::Result<()>;
+    async fn upload(&self, key: &str, path: &Path) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Default)]
+pub struct ObjectHead {
+    // unix seconds.
+    pub last_modified: i64,
+    // user metadata, e.g. `keywords` of an uploaded video.
+    pub metadata: HashMap<String, String>,
+}
+
+// `STORAGE_ROOT` keeps the objects in a local directory, otherwise they are in the bucket of `BUCKET_NAME`.
+pub async fn object_store_from_env() -> Box<dyn ObjectStore> {
+    if let Ok(root) = dotenv::var("STORAGE_ROOT") {
+        return Box::new(LocalObjectStore::new(Path::new(&root).join("objects")));
+    }
+
+    let bucket_name = dotenv::var("BUCKET_NAME")
+        .expect("BUCKET_NAME must be set.");
+    let shared_config = aws_config::from_env().load().await;
+    Box::new(S3ObjectStore::new(aws_sdk_s3::Client::new(&shared_config), &bucket_name))
+}
+
+pub async fn get_text(store: &dyn ObjectStore, key: &str) -> anyhow::Result<String> {
+    Ok(String::from_utf8(store.get(key).await?)?)
+}
+
+// names that become file names, e.g. a lang, a video id or a spool, so only plain identifiers are accepted.
+pub fn check_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        bail!("invalid name: {}", name);
+    }
+    Ok(())
+}
+
+pub struct S3ObjectStore {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+}
+
+impl S3ObjectStore {
+    pub fn new(client: aws_sdk_s3::Client, bucket: &str) -> Self {
+        S3ObjectStore { client, bucket: bucket.to_string() }
+    }
+}
+
+#[async_trait]
+impl ObjectStore for S3ObjectStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> anyhow::Result<()> {
+        self.client.put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(ByteStream::from(bytes))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        let output = self.client.get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await?;
+
+        Ok(output.body.collect().await?.into_bytes().to_vec())
+    }
+
+    async fn list(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        let mut keys = vec![];
+        let mut continuation_token = None;
+        loop {
+            let output = self.client.list_objects_v2()
+                .bucket(&self.bucket)
+                .prefix(prefix)
+                .set_continuation_token(continuation_token)
+                .send()
+                .await?;
+
+            keys.extend(output.contents().unwrap_or_default()
+                .iter()
+                .filter_map(|object| object.key())
+                .map(|key| key.to_string()));
+
+            continuation_token = output.next_continuation_token().map(|t| t.to_string());
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.client.delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    async fn head(&self, key: &str) -> anyhow::Result<ObjectHead> {
+        let output = self.client.head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await?;
+
+        Ok(ObjectHead {
+            last_modified: output.last_modified().map(|t| t.secs()).unwrap_or_default(),
+            metadata: output.metadata().cloned().unwrap_or_default(),
+        })
+    }
+
+    async fn download(&self, key: &str, path: &Path) -> anyhow::Result<()> {
+        let output = self.client.get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await?;
+
+        let mut r = output.body.into_async_read();
+        let mut w = BufWriter::new(tokio::fs::File::create(path).await?);
+        tokio::io::copy(&mut r, &mut w).await?;
+        w.flush().await?;
+
+        Ok(())
+    }
+
+    async fn upload(&self, key: &str, path: &Path) -> anyhow::Result<()> {
+        self.client.put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(ByteStream::from_path(path).await?)
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
+
+// keeps objects as files under a local directory, in place of the bucket. there is no user metadata.
+pub struct LocalObjectStore {
+    root: PathBuf,
+}
+
+impl LocalObjectStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalObjectStore { root: root.into() }
+    }
+
+    // keys are relative paths under the root, so `..`, absolute and empty keys are rejected.
+    fn file(&self, key: &str) -> anyhow::Result<PathBuf> {
+        let relative = Path::new(key);
+        if key.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            bail!("invalid object key: {}", key);
+        }
+
+        Ok(self.root.join(relative))
+    }
+
+    // the file of the key, with its directory created for a write.
+    fn path(&self, key: &str) -> anyhow::Result<PathBuf> {
+        let path = self.file(key)?;
+        fs::create_dir_all(path.parent().unwrap())?;
+
+        Ok(path)
+    }
+}
+
+#[async_trait]
+impl ObjectStore for LocalObjectStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> anyhow::Result<()> {
+        fs::write(self.path(key)?, bytes)?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(fs::read(self.file(key)?)?)
+    }
+
+    async fn list(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        let mut keys = vec![];
+        let mut dirs = vec![self.root.clone()];
+        while let Some(dir) = dirs.pop() {
+            if !dir.exists() {
+                continue;
+            }
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let key = path.strip_prefix(&self.root)?.to_string_lossy().to_string();
+                if key.starts_with(prefix) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys.sort();
+
+        Ok(keys)
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match fs::remove_file(self.file(key)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn head(&self, key: &str) -> anyhow::Result<ObjectHead> {
+        let modified = fs::metadata(self.file(key)?)?.modified()?;
+        let last_modified = modified.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+
+        Ok(ObjectHead { last_modified, metadata: HashMap::new() })
+    }
+
+    async fn download(&self, key: &str, path: &Path) -> anyhow::Result<()> {
+        fs::copy(self.file(key)?, path)?;
+
+        Ok(())
+    }
+
+    async fn upload(&self, key: &str, path: &Path) -> anyhow::Result<()> {
+        fs::copy(path, self.path(key)?)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{LocalObjectStore, ObjectStore};
+    use crate::test_dir::TestDir;
+
+    #[tokio::test]
+    async fn local_object_store_test() {
+        let dir = TestDir::new("storage-test");
+        let root = dir.path();
+        let store = LocalObjectStore::new(root.join("objects"));
+        store.put("subtitle/a/en.vtt", b"WEBVTT".to_vec()).await.unwrap();
+        store.put("subtitle/b/en.vtt", b"WEBVTT".to_vec()).await.unwrap();
+        store.put("transcription/a", b"{}".to_vec()).await.unwrap();
+
+        assert_eq!(store.list("subtitle/").await.unwrap(), vec!["subtitle/a/en.vtt", "subtitle/b/en.vtt"]);
+        assert!(store.head("transcription/a").await.unwrap().last_modified > 0);
+
+        let path = root.join("video.mp4");
+        store.download("subtitle/a/en.vtt", &path).await.unwrap();
+        store.upload("thumbnail/a.jpg", &path).await.unwrap();
+        assert_eq!(store.get("thumbnail/a.jpg").await.unwrap(), b"WEBVTT");
+
+        store.delete("subtitle/a/en.vtt").await.unwrap();
+        store.delete("subtitle/a/en.vtt").await.unwrap();
+        assert_eq!(store.list("subtitle/").await.unwrap(), vec!["subtitle/b/en.vtt"]);
+
+        for key in ["../catalogue.json", "subtitle/../../catalogue.json", "/etc/passwd", ""] {
+            assert!(store.put(key, vec![]).await.is_err());
+            assert!(store.get(key).await.is_err());
+            assert!(store.delete(key).await.is_err());
+        }
+    }
+}
\ No newline at end of file
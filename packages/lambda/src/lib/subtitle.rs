@@ -43,13 +43,16 @@ struct TranscribeJobResult {
 struct TranscribeJobTranscriptItem {
     r#type: String,
     #[serde(deserialize_with = "de_alternatives")]
-    alternatives: String,
+    alternatives: TranscribeJobTranscriptItemAlternative,
     #[serde(default)]
     #[serde(deserialize_with = "de_o_f32_from_str")]
     start_time: Option<f32>,
     #[serde(default)]
     #[serde(deserialize_with = "de_o_f32_from_str")]
-    end_time: Option<f32>
+    end_time: Option<f32>,
+    // set when the job identified speakers.
+    #[serde(default)]
+    speaker_label: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -60,17 +63,14 @@ struct TranscribeJobTranscriptItemAlternative {
     content: String
 }
 
-fn de_alternatives<'de, D>(deserializer: D) -> Result<String, D::Error>
+fn de_alternatives<'de, D>(deserializer: D) -> Result<TranscribeJobTranscriptItemAlternative, D::Error>
     where D: Deserializer<'de>
 {
     let v = Value::deserialize(deserializer)?;
     let arr = v.as_array().ok_or_else(|| de::Error::custom("alternatives must be array"))?;
     let head = arr.get(0).ok_or_else(|| de::Error::custom("alternatives must have head item"))?;
-    let obj = head.as_object().ok_or_else(|| de::Error::custom("alternative must be object"))?;
-    let obj_vaule = obj.get("content").ok_or_else(|| de::Error::custom("alternative must have content"))?;
-    let content = obj_vaule.as_str().ok_or_else(|| de::Error::custom("content must be string"))?;
 
-    Ok(content.to_string())
+    TranscribeJobTranscriptItemAlternative::deserialize(head).map_err(de::Error::custom)
 }
 
 fn de_f32_from_str<'de, D>(deserializer: D) -> Result<f32, D::Error>
@@ -96,9 +96,17 @@ pub struct Subtitle {
 pub struct SubtitleItem {
     start_time: f32,
     end_time: f32,
-    content: String
+    content: String,
+    // mean confidence of the transcribed words, unknown for subtitles stored without it.
+    confidence: Option<f32>,
+    // speaker of most of the words.
+    speaker: Option<String>,
 }
 
+// keys of the cue identifier that keep the values of a cue in the vtt, e.g. `1 confidence=0.934 speaker=spk_0`.
+const CONFIDENCE_ID: &str = "confidence=";
+const SPEAKER_ID: &str = "speaker=";
+
 impl Subtitle {
     pub fn from_transcribe_output(json: &str) -> anyhow::Result<Subtitle> {
         let output = serde_json::from_str::<TranscribeJobOutput>(json)?;
@@ -106,30 +114,79 @@ impl Subtitle {
 
         let iter = output.results.items.into_iter();
         let mut item = SubtitleItem::default();
+        // punctuation has no confidence or speaker, only pronounced words count.
+        let mut confidences: Vec<f32> = vec![];
+        let mut speakers: Vec<String> = vec![];
         for it in iter {
+            let content = it.alternatives.content;
+            if it.r#type == "pronunciation" {
+                confidences.push(it.alternatives.confidence);
+                speakers.extend(it.speaker_label);
+            }
             if item.start_time == f32::default() {
                 item.start_time = it.start_time.unwrap_or(0f32);
                 item.end_time = it.end_time.unwrap_or(0f32);
-                item.content = it.alternatives;
-            } else if it.alternatives.as_str() != "." && it.alternatives.as_str() != "?" {
-                item.content = format!("{} {}", item.content, it.alternatives);
+                item.content = content;
+            } else if content.as_str() != "." && content.as_str() != "?" {
+                item.content = format!("{} {}", item.content, content);
                 if let Some(et) = it.end_time {
                     item.end_time = et;
                 }
             } else {
-                item.content = format!("{}{}", item.content, it.alternatives);
+                item.content = format!("{}{}", item.content, content);
+                item.confidence = Self::mean(&confidences);
+                item.speaker = Self::most_common(&speakers);
+                confidences.clear();
+                speakers.clear();
                 items.push(item.clone());
                 item = SubtitleItem::default();
             }
         }
 
         if item.start_time != f32::default() {
+            item.confidence = Self::mean(&confidences);
+            item.speaker = Self::most_common(&speakers);
             items.push(item);
         }
 
         Ok(Subtitle { items })
     }
 
+    // parses the vtt written by `vtt`, so the index can be rebuilt from the stored subtitles.
+    pub fn from_vtt(vtt: &str) -> anyhow::Result<Subtitle> {
+        let mut items = vec![];
+        let mut identifier = "";
+        let mut lines = vtt.lines();
+        while let Some(line) = lines.next() {
+            let (start, end) = match line.split_once(" --> ") {
+                Some(times) => times,
+                None => {
+                    // the identifier line comes right before the timing of its cue.
+                    identifier = line;
+                    continue;
+                }
+            };
+            let value = |key: &str| identifier.split_whitespace()
+                .find_map(|setting| setting.strip_prefix(key))
+                .map(|v| v.to_string());
+            let content = lines.by_ref()
+                .take_while(|l| !l.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            items.push(SubtitleItem {
+                start_time: Self::parse_time(start)?,
+                end_time: Self::parse_time(end)?,
+                content,
+                confidence: value(CONFIDENCE_ID).and_then(|c| c.parse::<f32>().ok()),
+                speaker: value(SPEAKER_ID),
+            });
+            identifier = "";
+        }
+
+        Ok(Subtitle { items })
+    }
+
     pub async fn translate(&mut self, client: &Client,
                            source_language_code: &str,
                            target_language_code: &str) -> anyhow::Result<()> {
@@ -189,7 +246,17 @@ impl Subtitle {
 
         vtt.push_str("WEBVTT\n\n");
 
-        for (_i, item) in self.items.iter().enumerate() {
+        for (i, item) in self.items.iter().enumerate() {
+            let mut identifier = String::new();
+            if let Some(confidence) = item.confidence {
+                identifier.push_str(&format!(" {}{:.3}", CONFIDENCE_ID, confidence));
+            }
+            if let Some(speaker) = item.speaker.as_ref() {
+                identifier.push_str(&format!(" {}{}", SPEAKER_ID, speaker));
+            }
+            if !identifier.is_empty() {
+                vtt.push_str(&format!("{}{}\n", i + 1, identifier));
+            }
             vtt.push_str(&format!("{} --> {}\n",
                                   Self::time_format(item.start_time, "."),
                                   Self::time_format(item.end_time, ".")));
@@ -211,6 +278,42 @@ impl Subtitle {
         body
     }
 
+    // confidence of every line of `index_body`, 1 when it is unknown.
+    pub fn index_confidences(&self) -> Vec<f32> {
+        self.items.iter()
+            .map(|item| item.confidence.unwrap_or(1f32))
+            .collect()
+    }
+
+    // speaker of every line of `index_body`, empty when it is unknown.
+    pub fn index_speakers(&self) -> Vec<String> {
+        self.items.iter()
+            .map(|item| item.speaker.clone().unwrap_or_default())
+            .collect()
+    }
+
+    fn most_common(values: &[String]) -> Option<String> {
+        let mut counts: Vec<(&String, usize)> = vec![];
+        for value in values {
+            match counts.iter_mut().find(|(v, _)| *v == value) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((value, 1)),
+            }
+        }
+        // the first of equally common values wins.
+        counts.into_iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map(|(v, _)| v.clone())
+    }
+
+    fn mean(values: &[f32]) -> Option<f32> {
+        if values.is_empty() {
+            return None;
+        }
+        Some(values.iter().sum::<f32>() / values.len() as f32)
+    }
+
     fn time_format(t: f32, d: &str) -> String {
         let mut t = t;
         let h = (t as i32) / 3600;
@@ -223,6 +326,20 @@ impl Subtitle {
 
         format!("{:02}:{:02}:{:02}{}{:03}", h, m, s, d, ms)
     }
+
+    fn parse_time(time: &str) -> anyhow::Result<f32> {
+        let parse = |s: &str| s.trim().parse::<u32>()
+            .map_err(|e| anyhow::anyhow!("invalid time {}: {}", time, e));
+        let (hms, ms) = time.split_once(['.', ','])
+            .ok_or_else(|| anyhow::anyhow!("invalid time {}", time))?;
+        let mut seconds = 0;
+        for part in hms.split(':') {
+            seconds = seconds * 60 + parse(part)?;
+        }
+
+        // the half millisecond keeps `time_format`, which truncates, from printing the previous millisecond.
+        Ok(((seconds * 1000 + parse(ms)?) as f32 + 0.5) / 1000_f32)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -240,4 +357,41 @@ impl SubtitleQueueMessage {
             translate_language: translate_language.map(|s| s.to_owned())
         }
     }
+}
+
+pub fn subtitle_key(video_id: &str, lang: &str) -> String {
+    format!("subtitle/{}/{}.vtt", video_id, lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::subtitle::Subtitle;
+
+    #[test]
+    fn vtt_round_trip_test() {
+        let vtt = "WEBVTT\n\n00:00:01.300 --> 00:00:02.999\nhello world.\n\n01:02:03.045 --> 01:02:05.100\nbye?\n\n";
+        let subtitle = Subtitle::from_vtt(vtt).unwrap();
+
+        assert_eq!(subtitle.vtt(), vtt);
+        assert_eq!(subtitle.index_body(), "00:00:01.300 hello world.\n01:02:03.045 bye?\n");
+        assert_eq!(subtitle.index_confidences(), vec![1f32, 1f32]);
+    }
+
+    #[test]
+    fn cue_identifier_test() {
+        let json = r#"{"jobName": "job", "accountId": "1", "status": "COMPLETED", "results": {"items": [
+            {"type": "pronunciation", "start_time": "1.0", "end_time": "1.5", "speaker_label": "spk_1", "alternatives": [{"confidence": "1.0", "content": "hello"}]},
+            {"type": "pronunciation", "start_time": "1.5", "end_time": "2.0", "speaker_label": "spk_1", "alternatives": [{"confidence": "0.5", "content": "world"}]},
+            {"type": "punctuation", "alternatives": [{"confidence": "0.0", "content": "."}]}
+        ]}}"#;
+        let subtitle = Subtitle::from_transcribe_output(json).unwrap();
+        assert_eq!(subtitle.index_confidences(), vec![0.75f32]);
+        assert_eq!(subtitle.index_speakers(), vec!["spk_1".to_string()]);
+
+        let vtt = subtitle.vtt();
+        assert_eq!(vtt, "WEBVTT\n\n1 confidence=0.750 speaker=spk_1\n00:00:01.000 --> 00:00:02.000\nhello world.\n\n");
+        let stored = Subtitle::from_vtt(&vtt).unwrap();
+        assert_eq!(stored.index_confidences(), vec![0.75f32]);
+        assert_eq!(stored.index_speakers(), vec!["spk_1".to_string()]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,269 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use fst::{IntoStreamer, Map, MapBuilder, Streamer};
+use fst::automaton::{Automaton, Str};
+use fst::map::OpBuilder;
+use serde::{Serialize, Deserialize};
+use crate::storage::check_name;
+
+// completions are kept as fst maps of term or two word phrase -> frequency.
+//   {mount}/_suggest/{lang}/videos/{video_id}.fst  terms of a video
+//   {mount}/_suggest/{lang}/all.fst               union of every video in the language
+const SUGGEST_DIR: &str = "_suggest";
+const MIN_WORD_LEN: usize = 2;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Suggestion {
+    pub text: String,
+    pub count: u64,
+}
+
+fn lang_dir(mount: &str, lang: &str) -> PathBuf {
+    Path::new(mount).join(SUGGEST_DIR).join(lang)
+}
+
+fn video_path(mount: &str, lang: &str, video_id: &str) -> PathBuf {
+    lang_dir(mount, lang).join("videos").join(format!("{}.fst", video_id))
+}
+
+fn all_path(mount: &str, lang: &str) -> PathBuf {
+    lang_dir(mount, lang).join("all.fst")
+}
+
+pub fn count_terms(body: &str) -> BTreeMap<String, u64> {
+    let mut counts = BTreeMap::new();
+    for line in body.lines() {
+        // index body lines are `{time} {content}`
+        let content = line.split_once(' ').map(|(_, c)| c).unwrap_or(line).to_lowercase();
+        let words = content
+            .split(|c: char| !c.is_alphanumeric() && c != '\'')
+            .filter(|w| w.chars().count() >= MIN_WORD_LEN)
+            .collect::<Vec<_>>();
+
+        for word in words.iter() {
+            *counts.entry(word.to_string()).or_insert(0) += 1;
+        }
+        for pair in words.windows(2) {
+            *counts.entry(pair.join(" ")).or_insert(0) += 1;
+        }
+    }
+
+    counts
+}
+
+fn write_map<'a>(path: &Path, entries: impl Iterator<Item = (&'a [u8], u64)>) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // write aside and rename, so readers never see a half written file. the name is unique, so
+    // concurrent writers of the same map never write into one file.
+    let tmp_path = path.with_extension(format!("fst.{}.tmp", uuid::Uuid::new_v4()));
+    let mut builder = MapBuilder::new(BufWriter::new(fs::File::create(&tmp_path)?))?;
+    for (key, value) in entries {
+        builder.insert(key, value)?;
+    }
+    builder.finish()?;
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+fn open_map(path: &Path) -> anyhow::Result<Option<Map<Vec<u8>>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(Map::new(fs::read(path)?)?))
+}
+
+// the map of the language is only updated by `rebuild_lang_suggestions`, once for a batch of videos.
+pub fn index_video_suggestions(mount: &str, lang: &str, video_id: &str, body: &str) -> anyhow::Result<()> {
+    check_name(lang)?;
+    check_name(video_id)?;
+
+    let counts = count_terms(body);
+    write_map(&video_path(mount, lang, video_id),
+              counts.iter().map(|(k, v)| (k.as_bytes(), *v)))
+}
+
+pub fn remove_video_suggestions(mount: &str, lang: &str, video_id: &str) -> anyhow::Result<()> {
+    check_name(lang)?;
+    check_name(video_id)?;
+
+    let path = video_path(mount, lang, video_id);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+// reads every video of the language, so writers call it once per batch while they hold the
+// index writer lock of the language.
+pub fn rebuild_lang_suggestions(mount: &str, lang: &str) -> anyhow::Result<()> {
+    check_name(lang)?;
+    let videos_dir = lang_dir(mount, lang).join("videos");
+    let mut maps = vec![];
+    if videos_dir.exists() {
+        for entry in fs::read_dir(&videos_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("fst") {
+                if let Some(map) = open_map(&path)? {
+                    maps.push(map);
+                }
+            }
+        }
+    }
+
+    let mut op = OpBuilder::new();
+    for map in maps.iter() {
+        op.push(map.stream());
+    }
+
+    let mut entries: Vec<(Vec<u8>, u64)> = vec![];
+    let mut union = op.union();
+    while let Some((key, values)) = union.next() {
+        entries.push((key.to_vec(), values.iter().map(|v| v.value).sum()));
+    }
+
+    write_map(&all_path(mount, lang), entries.iter().map(|(k, v)| (k.as_slice(), *v)))
+}
+
+pub fn suggest(mount: &str, lang: &str, video_id: Option<&str>, prefix: &str, limit: usize) -> anyhow::Result<Vec<Suggestion>> {
+    check_name(lang)?;
+    let path = match video_id {
+        Some(video_id) => {
+            check_name(video_id)?;
+            video_path(mount, lang, video_id)
+        }
+        None => all_path(mount, lang),
+    };
+
+    let map = match open_map(&path)? {
+        Some(map) => map,
+        None => return Ok(vec![]),
+    };
+
+    // complete the last two words at most, the words before them are kept as typed.
+    let prefix = prefix.to_lowercase();
+    let words = prefix.split_whitespace().collect::<Vec<_>>();
+    if words.is_empty() {
+        return Ok(vec![]);
+    }
+    let split = words.len().saturating_sub(2);
+    let head = words[..split].join(" ");
+    let tail = words[split..].join(" ");
+
+    let mut stream = map.search(Str::new(&tail).starts_with()).into_stream();
+    let mut found: Vec<(String, u64)> = vec![];
+    while let Some((key, count)) = stream.next() {
+        found.push((String::from_utf8_lossy(key).to_string(), count));
+    }
+    found.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let suggestions = found.into_iter()
+        .filter(|(text, _)| text != &tail)
+        .take(limit)
+        .map(|(text, count)| Suggestion {
+            text: if head.is_empty() { text } else { format!("{} {}", head, text) },
+            count,
+        })
+        .collect();
+
+    Ok(suggestions)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::suggest::{count_terms, index_video_suggestions, rebuild_lang_suggestions, remove_video_suggestions, suggest, Suggestion, SUGGEST_DIR};
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn count_terms_test() {
+        let counts = count_terms("00:00:01.000 Free tier is free.\n00:00:03.000 The free tier.\n");
+
+        assert_eq!(counts.get("free"), Some(&3));
+        assert_eq!(counts.get("free tier"), Some(&2));
+        assert_eq!(counts.get("is"), Some(&1));
+    }
+
+    #[test]
+    fn suggest_test() {
+        let dir = TestDir::new("suggest-test");
+        let mount = dir.mount();
+
+        index_video_suggestions(mount, "en", "a", "00:00:01.000 free tier free trial\n").unwrap();
+        index_video_suggestions(mount, "en", "b", "00:00:01.000 free tier\n").unwrap();
+        rebuild_lang_suggestions(mount, "en").unwrap();
+
+        let all = suggest(mount, "en", None, "Fr", 2).unwrap();
+        assert_eq!(all, vec![
+            Suggestion { text: "free".to_string(), count: 3 },
+            Suggestion { text: "free tier".to_string(), count: 2 },
+        ]);
+
+        let video = suggest(mount, "en", Some("a"), "what free t", 10).unwrap();
+        assert_eq!(video.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(),
+                   vec!["what free tier", "what free trial"]);
+    }
+
+    #[test]
+    fn rebuild_lang_suggestions_test() {
+        let dir = TestDir::new("suggest-test");
+        let mount = dir.mount();
+
+        index_video_suggestions(mount, "en", "a", "00:00:01.000 lambda layers\n00:00:02.000 lambda\n").unwrap();
+        index_video_suggestions(mount, "en", "b", "00:00:01.000 lambda lab lab\n").unwrap();
+        // the language map is only written by the rebuild.
+        assert!(suggest(mount, "en", None, "la", 10).unwrap().is_empty());
+
+        // counts of both videos are summed, equal counts are ordered by text.
+        rebuild_lang_suggestions(mount, "en").unwrap();
+        let texts = |suggestions: Vec<Suggestion>| suggestions.into_iter()
+            .map(|s| (s.text, s.count))
+            .collect::<Vec<_>>();
+        assert_eq!(texts(suggest(mount, "en", None, "la", 10).unwrap()), vec![
+            ("lambda".to_string(), 3),
+            ("lab".to_string(), 2),
+            ("lab lab".to_string(), 1),
+            ("lambda lab".to_string(), 1),
+            ("lambda layers".to_string(), 1),
+            ("layers".to_string(), 1),
+        ]);
+
+        remove_video_suggestions(mount, "en", "b").unwrap();
+        rebuild_lang_suggestions(mount, "en").unwrap();
+        assert_eq!(texts(suggest(mount, "en", None, "la", 10).unwrap()), vec![
+            ("lambda".to_string(), 2),
+            ("lambda layers".to_string(), 1),
+            ("layers".to_string(), 1),
+        ]);
+
+        // temporary files are renamed away.
+        let names = std::fs::read_dir(Path::new(mount).join(SUGGEST_DIR).join("en")).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        assert!(names.iter().all(|n| !n.ends_with(".tmp")));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,177 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use aws_sdk_s3::types::SdkError;
+
+// how long a loaded list is trusted before asking the bucket whether it changed.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+pub fn synonym_key(lang: &str) -> String {
+    format!("synonym/{}.txt", lang)
+}
+
+#[derive(Default, Debug)]
+pub struct Synonyms {
+    groups: HashMap<String, Vec<String>>,
+}
+
+impl Synonyms {
+    // one group of equivalent terms per line, e.g. `k8s, kubernetes`. `#` starts a comment line.
+    pub fn parse(text: &str) -> Synonyms {
+        let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+        for line in text.lines().map(|l| l.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let terms = line.split(',')
+                .map(|t| t.trim().to_lowercase())
+                .filter(|t| !t.is_empty())
+                .collect::<Vec<_>>();
+
+            for term in terms.iter() {
+                let entry = groups.entry(term.clone()).or_default();
+                for other in terms.iter().filter(|t| *t != term) {
+                    if !entry.contains(other) {
+                        entry.push(other.clone());
+                    }
+                }
+            }
+        }
+
+        Synonyms { groups }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn expand_query(&self, query: &str) -> String {
+        let mut in_phrase = false;
+        query.split(' ')
+            .map(|word| {
+                let quotes = word.matches('"').count();
+                let expanded = if in_phrase || quotes > 0 {
+                    None
+                } else {
+                    self.expand_word(word)
+                };
+                if quotes % 2 == 1 {
+                    in_phrase = !in_phrase;
+                }
+                expanded.unwrap_or_else(|| word.to_string())
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn expand_word(&self, word: &str) -> Option<String> {
+        // keep `+`/`-` modifiers on the whole group.
+        let (modifier, term) = match word.strip_prefix(|c: char| c == '+' || c == '-') {
+            Some(rest) => (&word[..1], rest),
+            None => ("", word),
+        };
+
+        let synonyms = self.groups.get(&term.to_lowercase())?;
+        let alternatives = std::iter::once(term.to_string())
+            .chain(synonyms.iter().map(|s| {
+                if s.contains(' ') { format!("\"{}\"", s) } else { s.clone() }
+            }))
+            .collect::<Vec<_>>();
+
+        Some(format!("{}({})", modifier, alternatives.join(" OR ")))
+    }
+}
+
+struct CachedSynonyms {
+    synonyms: Arc<Synonyms>,
+    e_tag: Option<String>,
+    checked_at: Instant,
+}
+
+#[derive(Default)]
+pub struct SynonymCache {
+    entries: HashMap<String, CachedSynonyms>,
+}
+
+impl SynonymCache {
+    pub async fn get(&mut self, client: &aws_sdk_s3::Client, bucket: &str, lang: &str) -> anyhow::Result<Arc<Synonyms>> {
+        if let Some(cached) = self.entries.get(lang) {
+            if cached.checked_at.elapsed() < RELOAD_INTERVAL {
+                return Ok(cached.synonyms.clone());
+            }
+        }
+
+        let mut request = client.get_object()
+            .bucket(bucket)
+            .key(synonym_key(lang));
+        if let Some(e_tag) = self.entries.get(lang).and_then(|c| c.e_tag.as_ref()) {
+            request = request.if_none_match(e_tag);
+        }
+
+        let entry = match request.send().await {
+            Ok(output) => {
+                let e_tag = output.e_tag().map(|s| s.to_string());
+                let bs = output.body.collect().await?.into_bytes();
+                println!("synonyms for {} are loaded. e_tag: {:?}", lang, e_tag);
+                CachedSynonyms {
+                    synonyms: Arc::new(Synonyms::parse(std::str::from_utf8(&bs)?)),
+                    e_tag,
+                    checked_at: Instant::now(),
+                }
+            }
+            Err(e) if status_code(&e) == Some(304) => {
+                let mut cached = self.entries.remove(lang).expect("not modified requires cached synonyms");
+                cached.checked_at = Instant::now();
+                cached
+            }
+            Err(e) if status_code(&e) == Some(404) => CachedSynonyms {
+                synonyms: Arc::new(Synonyms::default()),
+                e_tag: None,
+                checked_at: Instant::now(),
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        let synonyms = entry.synonyms.clone();
+        self.entries.insert(lang.to_string(), entry);
+
+        Ok(synonyms)
+    }
+}
+
+fn status_code<E>(e: &SdkError<E>) -> Option<u16> {
+    e.raw_response().map(|r| r.http().status().as_u16())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::synonym::Synonyms;
+
+    #[test]
+    fn expand_query_test() {
+        let synonyms = Synonyms::parse("# devops\nk8s, kubernetes\naws, amazon web services\n");
+
+        assert_eq!(synonyms.expand_query("K8s pods"), "(K8s OR kubernetes) pods");
+        assert_eq!(synonyms.expand_query("-aws"), "-(aws OR \"amazon web services\")");
+        assert_eq!(synonyms.expand_query("\"deploy k8s\" k8s"), "\"deploy k8s\" (k8s OR kubernetes)");
+    }
+}
\ No newline at end of file
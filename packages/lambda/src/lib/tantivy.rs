@@ -16,11 +16,61 @@
  * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
  */
  
+use std::fmt;
 use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 use lindera_tantivy::mode::{Mode, Penalty};
 use lindera_tantivy::tokenizer::{DictionaryConfig, DictionaryKind, LinderaTokenizer, TokenizerConfig};
-use tantivy::Index;
-use tantivy::schema::{IndexRecordOption, Schema, STORED, STRING, TEXT, TextFieldIndexing, TextOptions};
+use tantivy::directory::error::LockError;
+use tantivy::directory::{Directory, DirectoryLock, INDEX_WRITER_LOCK};
+use tantivy::merge_policy::{LogMergePolicy, MergePolicy, NoMergePolicy};
+use tantivy::{doc, Index, IndexWriter, TantivyError};
+use tantivy::schema::{FAST, Facet, FacetOptions, IndexRecordOption, Schema, SchemaBuilder, STORED, STRING, TEXT, TextFieldIndexing, TextOptions};
+
+// video level documents (title, keywords) are kept apart from the subtitle language indexes.
+pub const VIDEO_INDEX: &str = "_video";
+
+// bump on every change of the language schemas. indexes of another version are rebuilt by the reindex function.
+// 1: video_id, time, body
+// 2: window
+// 3: confidence, created_at, video_key for ranking
+// 4: lang, speaker, uploaded facets
+pub const SCHEMA_VERSION: u32 = 4;
+pub const SCHEMA_VERSION_FILE: &str = "schema_version";
+
+#[derive(Debug)]
+pub struct SchemaMismatch {
+    pub lang: String,
+    pub version: u32,
+}
+
+impl fmt::Display for SchemaMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "index {} has schema version {}, but {} is required", self.lang, self.version, SCHEMA_VERSION)
+    }
+}
+
+impl std::error::Error for SchemaMismatch {}
+
+pub const INDEX_WRITER_HEAP: usize = 50_000_000;
+// the writer lock of an index on the shared mount may be held by another lambda. waits about
+// 30 seconds in total before giving up.
+const WRITER_LOCK_ATTEMPTS: u32 = 8;
+const WRITER_LOCK_BACKOFF: Duration = Duration::from_millis(250);
+
+#[derive(Debug)]
+pub struct WriterBusy {
+    pub attempts: u32,
+}
+
+impl fmt::Display for WriterBusy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "index writer is still locked by another writer after {} attempts", self.attempts)
+    }
+}
+
+impl std::error::Error for WriterBusy {}
 
 pub fn ko_text_option() -> TextOptions {
     TextOptions::default()
@@ -37,6 +87,9 @@ pub fn tantivy_en_schema() -> Schema {
     schema_builder.add_text_field("video_id", STRING | STORED);
     schema_builder.add_text_field("time", STRING | STORED);
     schema_builder.add_text_field("body", TEXT | STORED);
+    schema_builder.add_text_field("window", TEXT);
+    add_ranking_fields(&mut schema_builder);
+    add_facet_fields(&mut schema_builder);
     schema_builder.build()
 }
 
@@ -49,9 +102,47 @@ pub fn tantivy_ko_schema() -> Schema {
         "body",
         ko_text_option() | STORED
     );
+    schema_builder.add_text_field("window", ko_text_option());
+    add_ranking_fields(&mut schema_builder);
+    add_facet_fields(&mut schema_builder);
     schema_builder.build()
 }
 
+// read by `lib::ranking` while scoring.
+fn add_ranking_fields(schema_builder: &mut SchemaBuilder) {
+    schema_builder.add_f64_field("confidence", FAST);
+    // upload time of the video in unix seconds, 0 when unknown.
+    schema_builder.add_i64_field("created_at", FAST);
+    schema_builder.add_u64_field("video_key", FAST);
+}
+
+// facets that narrow search results: `/lang/{lang}`, `/speaker/{speaker}` and `/uploaded/{yyyy-mm}`.
+pub const FACET_FIELDS: [&str; 3] = ["lang", "speaker", "uploaded"];
+
+fn add_facet_fields(schema_builder: &mut SchemaBuilder) {
+    for name in FACET_FIELDS {
+        schema_builder.add_facet_field(name, FacetOptions::default());
+    }
+}
+
+// month of the upload time, none when it is unknown.
+pub fn upload_month(created_at: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(created_at, 0)
+        .filter(|_| created_at > 0)
+        .map(|t| t.format("%Y-%m").to_string())
+}
+
+// cues of one video with the values stored for ranking and facets. `confidences` and `speakers`
+// are aligned with `cues`, missing confidences count as 1 and empty speakers are left out.
+pub struct VideoCues<'a> {
+    pub video_id: &'a str,
+    pub lang: &'a str,
+    pub created_at: i64,
+    pub cues: &'a [(&'a str, &'a str)],
+    pub confidences: &'a [f32],
+    pub speakers: &'a [String],
+}
+
 pub fn tantivy_schema(lang: &str) -> Schema {
     if lang == "ko" {
         tantivy_ko_schema()
@@ -60,13 +151,67 @@ pub fn tantivy_schema(lang: &str) -> Schema {
     }
 }
 
+pub fn tantivy_video_schema() -> Schema {
+    let mut schema_builder = Schema::builder();
+
+    schema_builder.add_text_field("video_id", STRING | STORED);
+    schema_builder.add_text_field("title", TEXT | STORED);
+    schema_builder.add_text_field("lang", STRING | STORED);
+    schema_builder.add_text_field("keywords", TEXT | STORED);
+    schema_builder.build()
+}
+
+// fails with `SchemaMismatch` when the index on the mount was built by another schema version.
 pub fn tantivy_index(mount: &str, lang: &str) -> anyhow::Result<Index> {
+    let index = tantivy_index_without_tokenizers(mount, lang)?;
+    register_tokenizers(&index, lang)?;
 
-    let schema = tantivy_schema(lang);
+    Ok(index)
+}
+
+// for callers that reuse the tokenizers of an index opened before, see `lib::cache`.
+pub fn tantivy_index_without_tokenizers(mount: &str, lang: &str) -> anyhow::Result<Index> {
     let index_path = &format!("{}/{}", mount, lang);
     fs::create_dir_all(index_path)?;
+
+    let version = schema_version(index_path)?;
+    if version != SCHEMA_VERSION {
+        return Err(SchemaMismatch { lang: lang.to_string(), version }.into());
+    }
+
+    let dir = tantivy::directory::MmapDirectory::open(index_path)?;
+    let index = Index::open_or_create(dir, tantivy_schema(lang))?;
+
+    Ok(index)
+}
+
+// version of the index in the directory. an empty directory takes the current version.
+pub fn schema_version(index_path: &str) -> anyhow::Result<u32> {
+    let path = Path::new(index_path);
+    if !path.join("meta.json").exists() {
+        fs::write(path.join(SCHEMA_VERSION_FILE), SCHEMA_VERSION.to_string())?;
+        return Ok(SCHEMA_VERSION);
+    }
+
+    // indexes created before versioning have no file.
+    match fs::read_to_string(path.join(SCHEMA_VERSION_FILE)) {
+        Ok(version) => Ok(version.trim().parse()?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(1),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn open_index(index_path: &str, lang: &str) -> anyhow::Result<Index> {
+    let schema = tantivy_schema(lang);
     let dir = tantivy::directory::MmapDirectory::open(index_path)?;
     let index = Index::open_or_create(dir, schema)?;
+    register_tokenizers(&index, lang)?;
+
+    Ok(index)
+}
+
+// `lang_ko` loads the whole KoDic dictionary, which takes most of the time to open a korean index.
+pub fn register_tokenizers(index: &Index, lang: &str) -> anyhow::Result<()> {
     if lang == "ko" {
         let config = TokenizerConfig {
             dictionary: DictionaryConfig {
@@ -81,5 +226,173 @@ pub fn tantivy_index(mount: &str, lang: &str) -> anyhow::Result<Index> {
             .register("lang_ko", LinderaTokenizer::with_config(config)?);
     }
 
+    Ok(())
+}
+
+// retries with exponential backoff while another writer holds the lock and fails with `WriterBusy`,
+// so the messages can be redelivered instead of being lost.
+pub async fn index_writer(index: &Index) -> anyhow::Result<IndexWriter> {
+    let index_writer = retry_locked(|| index.writer(INDEX_WRITER_HEAP)).await?;
+    index_writer.set_merge_policy(merge_policy_from_env());
+
+    Ok(index_writer)
+}
+
+// takes the writer lock of the index directory without opening the index, which may be of another
+// schema version. writers of the index wait while it is held, like `index_writer`.
+pub async fn lock_index_dir(index_path: &Path) -> anyhow::Result<DirectoryLock> {
+    let dir = tantivy::directory::MmapDirectory::open(index_path)?;
+    retry_locked(|| dir.acquire_lock(&INDEX_WRITER_LOCK).map_err(|e| TantivyError::LockFailure(e, None))).await
+}
+
+async fn retry_locked<T>(acquire: impl Fn() -> tantivy::Result<T>) -> anyhow::Result<T> {
+    let mut backoff = WRITER_LOCK_BACKOFF;
+    for attempt in 1..=WRITER_LOCK_ATTEMPTS {
+        match acquire() {
+            Ok(locked) => return Ok(locked),
+            Err(TantivyError::LockFailure(LockError::LockBusy, _)) if attempt < WRITER_LOCK_ATTEMPTS => {
+                // jitter keeps writers that collided from retrying at the same time.
+                let jitter = SystemTime::now()
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .subsec_millis() as u64 % backoff.as_millis() as u64;
+                let delay = backoff + Duration::from_millis(jitter);
+                println!("index writer is busy, retry in {:?}", delay);
+                tokio::time::sleep(delay).await;
+                backoff *= 2;
+            }
+            Err(TantivyError::LockFailure(LockError::LockBusy, _)) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(WriterBusy { attempts: WRITER_LOCK_ATTEMPTS }.into())
+}
+
+// `MERGE_POLICY=none` leaves merging to the maintenance function. the log merge policy is tuned by
+// `MERGE_MIN_NUM_SEGMENTS`, `MERGE_MAX_DOCS_BEFORE_MERGE` and `MERGE_MIN_LAYER_SIZE`.
+pub fn merge_policy_from_env() -> Box<dyn MergePolicy> {
+    if dotenv::var("MERGE_POLICY").map(|p| p == "none").unwrap_or(false) {
+        return Box::new(NoMergePolicy);
+    }
+
+    let env = |name: &str| dotenv::var(name).ok().and_then(|v| v.parse::<usize>().ok());
+    let mut merge_policy = LogMergePolicy::default();
+    if let Some(min_num_segments) = env("MERGE_MIN_NUM_SEGMENTS") {
+        merge_policy.set_min_num_segments(min_num_segments);
+    }
+    if let Some(max_docs_before_merge) = env("MERGE_MAX_DOCS_BEFORE_MERGE") {
+        merge_policy.set_max_docs_before_merge(max_docs_before_merge);
+    }
+    if let Some(min_layer_size) = env("MERGE_MIN_LAYER_SIZE") {
+        merge_policy.set_min_layer_size(min_layer_size as u32);
+    }
+
+    Box::new(merge_policy)
+}
+
+pub fn tantivy_video_index(mount: &str) -> anyhow::Result<Index> {
+    let index_path = &format!("{}/{}", mount, VIDEO_INDEX);
+    fs::create_dir_all(index_path)?;
+    let dir = tantivy::directory::MmapDirectory::open(index_path)?;
+    let index = Index::open_or_create(dir, tantivy_video_schema())?;
+
     Ok(index)
+}
+
+// (time, body) of every cue in the body of `IndexTopicMessage`.
+pub fn body_cues(body: &str) -> Vec<(&str, &str)> {
+    body.lines()
+        .filter_map(|line| line.split_once(' '))
+        .collect()
+}
+
+pub fn add_cue_documents(index_writer: &IndexWriter, schema: &Schema, video: &VideoCues) -> anyhow::Result<()> {
+    let VideoCues { video_id, lang, created_at, cues, confidences, speakers } = *video;
+    let video_id_field = schema.get_field("video_id").unwrap();
+    let time_field = schema.get_field("time").unwrap();
+    let body_field = schema.get_field("body").unwrap();
+    let window_field = schema.get_field("window").unwrap();
+    let confidence_field = schema.get_field("confidence").unwrap();
+    let created_at_field = schema.get_field("created_at").unwrap();
+    let video_key_field = schema.get_field("video_key").unwrap();
+    let video_key = crate::ranking::video_key(video_id);
+    let lang_field = schema.get_field("lang").unwrap();
+    let speaker_field = schema.get_field("speaker").unwrap();
+    let uploaded_field = schema.get_field("uploaded").unwrap();
+    let uploaded = upload_month(created_at);
+
+    for (i, (time, body)) in cues.iter().enumerate() {
+        // window is the cue followed by the next cue, for phrases spanning the boundary.
+        let window = match cues.get(i + 1) {
+            Some((_, next)) => format!("{} {}", body, next),
+            None => body.to_string(),
+        };
+        let mut document = doc! {
+            video_id_field => video_id,
+            time_field => *time,
+            body_field => *body,
+            window_field => window,
+            confidence_field => confidences.get(i).copied().unwrap_or(1f32) as f64,
+            created_at_field => created_at,
+            video_key_field => video_key
+        };
+        document.add_facet(lang_field, Facet::from_path(["lang", lang]));
+        if let Some(speaker) = speakers.get(i).filter(|s| !s.is_empty()) {
+            document.add_facet(speaker_field, Facet::from_path(["speaker", speaker.as_str()]));
+        }
+        if let Some(month) = uploaded.as_deref() {
+            document.add_facet(uploaded_field, Facet::from_path(["uploaded", month]));
+        }
+        index_writer.add_document(document)?;
+    }
+
+    Ok(())
+}
+
+// every language has its own index directory under the mount.
+pub fn indexed_langs(mount: &str) -> anyhow::Result<Vec<String>> {
+    let mut langs = vec![];
+    for entry in fs::read_dir(mount)? {
+        let entry = entry?;
+        if !entry.path().join("meta.json").exists() {
+            continue;
+        }
+        if let Some(lang) = entry.file_name().to_str().filter(|name| !name.starts_with('_')) {
+            langs.push(lang.to_string());
+        }
+    }
+    langs.sort();
+
+    Ok(langs)
+}
+
+#[cfg(test)]
+mod tests {
+    use tantivy::Index;
+    use tantivy::schema::{Schema, STORED, STRING, TEXT};
+    use crate::tantivy::{schema_version, tantivy_index, SchemaMismatch, SCHEMA_VERSION};
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn schema_version_test() {
+        let dir = TestDir::new("tantivy-test");
+        let mount = dir.mount();
+
+        // a new index takes the current version.
+        tantivy_index(mount, "en").unwrap();
+        assert_eq!(schema_version(&format!("{}/en", mount)).unwrap(), SCHEMA_VERSION);
+
+        // an index of the first schema, without `window`, is reported for rebuilding instead of failing to open.
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("video_id", STRING | STORED);
+        schema_builder.add_text_field("time", STRING | STORED);
+        schema_builder.add_text_field("body", TEXT | STORED);
+        let path = format!("{}/de", mount);
+        std::fs::create_dir_all(&path).unwrap();
+        Index::create_in_dir(&path, schema_builder.build()).unwrap();
+
+        let error = tantivy_index(mount, "de").unwrap_err();
+        assert_eq!(error.downcast_ref::<SchemaMismatch>().unwrap().version, 1);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,58 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait Translator: Send + Sync {
+    async fn translate(&self, text: &str, source_language_code: &str, target_language_code: &str) -> anyhow::Result<String>;
+}
+
+pub struct AwsTranslator {
+    client: aws_sdk_translate::Client,
+}
+
+impl AwsTranslator {
+    pub fn new(client: aws_sdk_translate::Client) -> Self {
+        AwsTranslator { client }
+    }
+}
+
+#[async_trait]
+impl Translator for AwsTranslator {
+    async fn translate(&self, text: &str, source_language_code: &str, target_language_code: &str) -> anyhow::Result<String> {
+        let output = self.client.translate_text()
+            .source_language_code(source_language_code)
+            .target_language_code(target_language_code)
+            .text(text)
+            .send()
+            .await?;
+
+        Ok(output.translated_text.unwrap_or_default())
+    }
+}
+
+// keeps the text as it is, for searching other languages with the original words.
+pub struct IdentityTranslator;
+
+#[async_trait]
+impl Translator for IdentityTranslator {
+    async fn translate(&self, text: &str, _source_language_code: &str, _target_language_code: &str) -> anyhow::Result<String> {
+        Ok(text.to_string())
+    }
+}
\ No newline at end of file
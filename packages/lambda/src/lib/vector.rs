@@ -0,0 +1,345 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: MIT-0
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy of this
+ * software and associated documentation files (the "Software"), to deal in the Software
+ * without restriction, including without limitation the rights to use, copy, modify,
+ * merge, publish, distribute, sublicense, and/or sell copies of the Software, and to
+ * permit persons to whom the Software is furnished to do so.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED,
+ * INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+ * PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT
+ * HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+ * OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+ * SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+ */
+ 
+use std::collections::HashMap;
+use std::fs;
+use std::hash::Hash;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use anyhow::bail;
+use crate::storage::check_name;
+
+// per cue embeddings are stored next to the tantivy indexes, one file per video.
+//   {mount}/_vector/{lang}/{video_id}.vec
+const VECTOR_DIR: &str = "_vector";
+const MAGIC: &[u8; 4] = b"VEC1";
+// the constant of reciprocal rank fusion, which dampens the weight of the top ranks.
+pub const RRF_K: f32 = 60.0;
+
+pub trait Embedder: Send + Sync {
+    fn dimension(&self) -> usize;
+    fn embed(&self, text: &str) -> Vec<f32>;
+}
+
+// deterministic embedder hashing words and character trigrams into signed buckets.
+// it needs no model, so it works offline and in tests.
+pub struct HashingEmbedder {
+    dimension: usize,
+}
+
+impl HashingEmbedder {
+    pub fn new(dimension: usize) -> Self {
+        HashingEmbedder { dimension }
+    }
+
+    fn add_feature(&self, vector: &mut [f32], feature: &str, weight: f32) {
+        let hash = fnv1a(feature.as_bytes());
+        let bucket = (hash % self.dimension as u64) as usize;
+        let sign = if (hash >> 63) == 0 { 1f32 } else { -1f32 };
+        vector[bucket] += sign * weight;
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0f32; self.dimension];
+        let text = text.to_lowercase();
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            self.add_feature(&mut vector, word, 1.0);
+
+            let chars = format!(" {} ", word).chars().collect::<Vec<_>>();
+            for trigram in chars.windows(3) {
+                self.add_feature(&mut vector, &trigram.iter().collect::<String>(), 0.5);
+            }
+        }
+
+        normalize(&mut vector);
+        vector
+    }
+}
+
+// `EMBEDDER=hashing` enables the vector index, it is off when the variable is not set.
+pub fn embedder_from_env() -> Option<Box<dyn Embedder>> {
+    match dotenv::var("EMBEDDER").ok()?.as_str() {
+        "hashing" => Some(Box::new(HashingEmbedder::new(256))),
+        other => {
+            println!("unknown embedder: {}", other);
+            None
+        }
+    }
+}
+
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325_u64;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0f32 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CueVector {
+    pub time: String,
+    pub body: String,
+    pub vector: Vec<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VectorHit {
+    pub video_id: String,
+    pub time: String,
+    pub body: String,
+    pub score: f32,
+}
+
+// lang and video_id become file names, see `lib::storage::check_name`.
+fn lang_dir(mount: &str, lang: &str) -> anyhow::Result<PathBuf> {
+    check_name(lang)?;
+    Ok(Path::new(mount).join(VECTOR_DIR).join(lang))
+}
+
+fn video_path(mount: &str, lang: &str, video_id: &str) -> anyhow::Result<PathBuf> {
+    check_name(video_id)?;
+    Ok(lang_dir(mount, lang)?.join(format!("{}.vec", video_id)))
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> std::io::Result<()> {
+    w.write_all(&(s.len() as u32).to_le_bytes())?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_u32(r: &mut impl Read) -> std::io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_str(r: &mut impl Read) -> anyhow::Result<String> {
+    let mut buf = vec![0u8; read_u32(r)? as usize];
+    r.read_exact(&mut buf)?;
+    Ok(String::from_utf8(buf)?)
+}
+
+pub fn write_vectors(path: &Path, dimension: usize, cues: &[CueVector]) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp_path = path.with_extension("vec.tmp");
+    let mut w = BufWriter::new(fs::File::create(&tmp_path)?);
+    w.write_all(MAGIC)?;
+    w.write_all(&(dimension as u32).to_le_bytes())?;
+    w.write_all(&(cues.len() as u32).to_le_bytes())?;
+    for cue in cues {
+        write_str(&mut w, &cue.time)?;
+        write_str(&mut w, &cue.body)?;
+        for v in cue.vector.iter() {
+            w.write_all(&v.to_le_bytes())?;
+        }
+    }
+    w.flush()?;
+    drop(w);
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+pub fn read_vectors(path: &Path) -> anyhow::Result<(usize, Vec<CueVector>)> {
+    let bytes = fs::read(path)?;
+    let mut r = bytes.as_slice();
+
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        bail!("{} is not a vector file", path.display());
+    }
+
+    let dimension = read_u32(&mut r)? as usize;
+    let count = read_u32(&mut r)? as usize;
+    let mut cues = Vec::with_capacity(count);
+    for _ in 0..count {
+        let time = read_str(&mut r)?;
+        let body = read_str(&mut r)?;
+        let mut vector = Vec::with_capacity(dimension);
+        for _ in 0..dimension {
+            let mut buf = [0u8; 4];
+            r.read_exact(&mut buf)?;
+            vector.push(f32::from_le_bytes(buf));
+        }
+        cues.push(CueVector { time, body, vector });
+    }
+
+    Ok((dimension, cues))
+}
+
+pub fn index_video_vectors(mount: &str, lang: &str, video_id: &str, cues: &[(&str, &str)], embedder: &dyn Embedder) -> anyhow::Result<()> {
+    let path = video_path(mount, lang, video_id)?;
+    let cues = cues.iter()
+        .map(|(time, body)| CueVector {
+            time: time.to_string(),
+            body: body.to_string(),
+            vector: embedder.embed(body),
+        })
+        .collect::<Vec<_>>();
+
+    write_vectors(&path, embedder.dimension(), &cues)
+}
+
+pub fn remove_video_vectors(mount: &str, lang: &str, video_id: &str) -> anyhow::Result<()> {
+    let path = video_path(mount, lang, video_id)?;
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+// brute force nearest neighbours over the cues of one video, or every video of the language.
+pub fn search(mount: &str, lang: &str, video_id: Option<&str>, text: &str, limit: usize, embedder: &dyn Embedder) -> anyhow::Result<Vec<VectorHit>> {
+    let paths = match video_id {
+        Some(video_id) => vec![video_path(mount, lang, video_id)?],
+        None => {
+            let dir = lang_dir(mount, lang)?;
+            if !dir.exists() {
+                return Ok(vec![]);
+            }
+            fs::read_dir(dir)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("vec"))
+                .collect()
+        }
+    };
+
+    let query = embedder.embed(text);
+    let mut hits: Vec<VectorHit> = vec![];
+    for path in paths.iter().filter(|p| p.exists()) {
+        let (dimension, cues) = read_vectors(path)?;
+        if dimension != embedder.dimension() {
+            println!("skip {}, dimension {} != {}", path.display(), dimension, embedder.dimension());
+            continue;
+        }
+
+        let video_id = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        for cue in cues {
+            hits.push(VectorHit {
+                video_id: video_id.to_string(),
+                score: dot(&query, &cue.vector),
+                time: cue.time,
+                body: cue.body,
+            });
+        }
+    }
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+
+    Ok(hits)
+}
+
+// merges rankings by the sum of 1 / (k + rank) of each key.
+pub fn reciprocal_rank_fusion<K: Eq + Hash + Clone>(rankings: &[Vec<K>], k: f32) -> Vec<(K, f32)> {
+    let mut scores: HashMap<K, f32> = HashMap::new();
+    let mut order: Vec<K> = vec![];
+    for ranking in rankings {
+        for (rank, key) in ranking.iter().enumerate() {
+            let score = scores.entry(key.clone()).or_insert_with(|| {
+                order.push(key.clone());
+                0f32
+            });
+            *score += 1f32 / (k + rank as f32 + 1f32);
+        }
+    }
+
+    let mut fused = order.into_iter()
+        .map(|key| {
+            let score = scores[&key];
+            (key, score)
+        })
+        .collect::<Vec<_>>();
+    // stable sort keeps the first seen key ahead on ties.
+    fused.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vector::{CueVector, dot, Embedder, HashingEmbedder, index_video_vectors, read_vectors, reciprocal_rank_fusion, remove_video_vectors, search, write_vectors};
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn hashing_embedder_test() {
+        let embedder = HashingEmbedder::new(64);
+        let a = embedder.embed("Deploying containers on Kubernetes");
+        let b = embedder.embed("deploying a container to kubernetes");
+        let c = embedder.embed("baking sourdough bread");
+
+        assert_eq!(a, embedder.embed("Deploying containers on Kubernetes"));
+        assert!((dot(&a, &a) - 1.0).abs() < 1e-5);
+        assert!(dot(&a, &b) > dot(&a, &c));
+    }
+
+    #[test]
+    fn vectors_round_trip_test() {
+        let dir = TestDir::new("vector-test");
+        let path = dir.path().join("a.vec");
+        let cues = vec![CueVector { time: "00:00:01.000".to_string(), body: "hello".to_string(), vector: vec![0.5, -0.5] }];
+
+        write_vectors(&path, 2, &cues).unwrap();
+        assert_eq!(read_vectors(&path).unwrap(), (2, cues));
+    }
+
+    #[test]
+    fn video_path_test() {
+        let dir = TestDir::new("vector-test");
+        let mount = dir.mount();
+        let embedder = HashingEmbedder::new(8);
+
+        assert!(index_video_vectors(mount, "en", "../a", &[("00:00:01.000", "hello")], &embedder).is_err());
+        assert!(index_video_vectors(mount, "../en", "a", &[("00:00:01.000", "hello")], &embedder).is_err());
+        assert!(remove_video_vectors(mount, "en", "/etc/passwd").is_err());
+        assert!(search(mount, "..", None, "hello", 1, &embedder).is_err());
+        assert!(!std::path::Path::new(mount).exists());
+
+        index_video_vectors(mount, "en", "a", &[("00:00:01.000", "hello")], &embedder).unwrap();
+        assert_eq!(search(mount, "en", Some("a"), "hello", 1, &embedder).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reciprocal_rank_fusion_test() {
+        let fused = reciprocal_rank_fusion(&[vec!["a", "b", "c"], vec!["b", "c"]], 60.0);
+        let keys = fused.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+
+        assert_eq!(keys, vec!["b", "c", "a"]);
+    }
+}
\ No newline at end of file